@@ -0,0 +1,74 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lib_poki_launcher::db::SearchIndex;
+use lib_poki_launcher::prelude::*;
+
+const NUM_APPS: usize = 10_000;
+
+const WORDS: &[&str] = &[
+    "Text", "Editor", "Image", "Viewer", "Music", "Player", "Web", "Browser", "Terminal", "Office",
+    "Writer", "Mail", "Client", "Video", "Studio", "Code", "Manager", "Settings",
+];
+
+/// A database of apps with names made of two or three words.
+fn synthetic_db() -> AppsDB {
+    let apps = (0..NUM_APPS)
+        .map(|i| {
+            let name = format!(
+                "{} {} {}",
+                WORDS[i % WORDS.len()],
+                WORDS[(i / WORDS.len()) % WORDS.len()],
+                i
+            );
+            App::new(name, "icon".to_owned(), format!("/usr/bin/app-{}", i))
+        })
+        .collect();
+    let mut apps_db = AppsDB::new(apps);
+    for app in apps_db.apps.clone().iter().step_by(7) {
+        apps_db.update(app);
+    }
+    apps_db
+}
+
+/// Search for `query` one keystroke at a time.
+fn type_query(c: &mut Criterion, apps_db: &AppsDB, query: &str) {
+    c.bench_function(&format!("get_ranked_list {:?}", query), |b| {
+        b.iter(|| {
+            for end in 1..=query.len() {
+                black_box(apps_db.get_ranked_list(&query[..end], Some(5)));
+            }
+        })
+    });
+    c.bench_function(&format!("index {:?}", query), |b| {
+        b.iter(|| {
+            let mut index = SearchIndex::new(apps_db);
+            for end in 1..=query.len() {
+                black_box(index.search(apps_db, &query[..end], 5).len());
+            }
+        })
+    });
+}
+
+fn search(c: &mut Criterion) {
+    let apps_db = synthetic_db();
+    type_query(c, &apps_db, "term");
+    type_query(c, &apps_db, "web browser");
+}
+
+criterion_group!(benches, search);
+criterion_main!(benches);
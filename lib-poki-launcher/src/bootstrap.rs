@@ -0,0 +1,317 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::db::interchange::Record;
+use crate::{App, HOME_PATH};
+use failure::{Error, Fail};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Launches credited to the most used app, the others are scaled to match.
+///
+/// Other launchers and shells count launches over all time, so the counts
+/// are scaled down to keep them from outweighing real use for months.
+pub const SEED_LAUNCHES: f64 = 10.0;
+
+/// Marks a byte that zsh stored xor 0x20 in its history file.
+const ZSH_META: u8 = 0x83;
+
+/// An error from reading the usage of another launcher or shell.
+#[derive(Debug, Fail)]
+pub enum BootstrapError {
+    #[fail(
+        display = "Unknown source {}, expected rofi, ulauncher, bash, zsh or fish",
+        name
+    )]
+    UnknownSource { name: String },
+}
+
+/// Another launcher or shell to read app usage from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// Launch counts from rofi's drun mode.
+    Rofi,
+    /// Launch counts from ulauncher.
+    Ulauncher,
+    /// Commands from the bash history.
+    Bash,
+    /// Commands from the zsh history.
+    Zsh,
+    /// Commands from the fish history.
+    Fish,
+}
+
+impl FromStr for Source {
+    type Err = BootstrapError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "rofi" => Ok(Source::Rofi),
+            "ulauncher" => Ok(Source::Ulauncher),
+            "bash" => Ok(Source::Bash),
+            "zsh" => Ok(Source::Zsh),
+            "fish" => Ok(Source::Fish),
+            _ => Err(BootstrapError::UnknownSource {
+                name: name.to_owned(),
+            }),
+        }
+    }
+}
+
+impl Source {
+    /// Where the source keeps its usage by default.
+    pub fn default_path(self) -> PathBuf {
+        HOME_PATH.join(match self {
+            Source::Rofi => ".cache/rofi3.druncache",
+            Source::Ulauncher => ".local/share/ulauncher/app_stat_v2.json",
+            Source::Bash => ".bash_history",
+            Source::Zsh => ".zsh_history",
+            Source::Fish => ".local/share/fish/fish_history",
+        })
+    }
+
+    /// If the source counts desktop entries rather than commands.
+    fn counts_entries(self) -> bool {
+        match self {
+            Source::Rofi | Source::Ulauncher => true,
+            Source::Bash | Source::Zsh | Source::Fish => false,
+        }
+    }
+
+    /// Count the uses of each desktop entry or command in `text`.
+    ///
+    /// Desktop entries are counted by file name and commands by the
+    /// file name of the program run.
+    fn parse(self, text: &str) -> Result<HashMap<String, f64>, Error> {
+        let mut counts = HashMap::new();
+        match self {
+            // Lines of "<count> <desktop id or path>".
+            Source::Rofi => {
+                for line in text.lines() {
+                    let mut parts = line.trim().splitn(2, ' ');
+                    if let (Some(count), Some(entry)) = (parts.next(), parts.next()) {
+                        if let Ok(count) = count.parse::<f64>() {
+                            *counts.entry(file_name(entry)).or_insert(0.0) += count;
+                        }
+                    }
+                }
+            }
+            // A JSON object of desktop entry paths to counts.
+            Source::Ulauncher => {
+                let stats: HashMap<String, serde_json::Value> = serde_json::from_str(text)?;
+                for (entry, count) in stats {
+                    if let Some(count) = count.as_f64() {
+                        *counts.entry(file_name(&entry)).or_insert(0.0) += count;
+                    }
+                }
+            }
+            Source::Bash | Source::Zsh | Source::Fish => {
+                for command in text.lines().filter_map(|line| self.history_command(line)) {
+                    if let Some(name) = command_name(command) {
+                        *counts.entry(name.to_owned()).or_insert(0.0) += 1.0;
+                    }
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    /// The command on a line of a shell history file, if it has one.
+    fn history_command(self, line: &str) -> Option<&str> {
+        match self {
+            // Lines starting with # are timestamps.
+            Source::Bash if !line.starts_with('#') => Some(line),
+            // Extended history lines are ": <time>:<duration>;<command>".
+            Source::Zsh if line.starts_with(": ") => {
+                line.split_once(';').map(|(_, command)| command)
+            }
+            Source::Zsh => Some(line),
+            Source::Fish => line.trim_start().strip_prefix("- cmd: "),
+            _ => None,
+        }
+    }
+}
+
+/// Undo the escaping zsh does to bytes it writes to its history file.
+fn unmetafy(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == ZSH_META {
+            if let Some(&escaped) = bytes.next() {
+                out.push(escaped ^ 0x20);
+            }
+        } else {
+            out.push(byte);
+        }
+    }
+    out
+}
+
+/// The file name at the end of `path`.
+fn file_name(path: &str) -> String {
+    Path::new(path.trim())
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// The file name of the program `command` runs.
+///
+/// Wrappers like `sudo` and `env` and leading variable assignments are
+/// skipped.
+fn command_name(command: &str) -> Option<&str> {
+    command
+        .split_whitespace()
+        .find(|word| {
+            !word.contains('=') && !["sudo", "env", "nohup", "exec", "command"].contains(word)
+        })
+        .and_then(|program| program.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+}
+
+/// Read the usage recorded by `source` in `data` as records to import.
+///
+/// Bytes that aren't valid UTF-8 are replaced, so a history with a few
+/// broken lines can still be read. Desktop entries are matched to apps by
+/// file name. Commands are matched to the app whose exec line runs the same
+/// program, and skipped if several apps do, such as apps all run by
+/// `flatpak`. The most used app gets `SEED_LAUNCHES` launches and the
+/// others a share of that in proportion to their uses.
+pub fn records(apps: &[App], source: Source, data: &[u8]) -> Result<Vec<Record>, Error> {
+    let data = match source {
+        Source::Zsh => Cow::Owned(unmetafy(data)),
+        _ => Cow::Borrowed(data),
+    };
+    let counts = source.parse(&String::from_utf8_lossy(&data))?;
+    let mut matched: Vec<(&App, f64)> = Vec::new();
+    for (key, count) in counts {
+        let mut candidates = apps.iter().filter(|app| {
+            if source.counts_entries() {
+                !app.desktop_id.is_empty() && app.has_id(&key)
+            } else {
+                command_name(&app.exec) == Some(key.as_str())
+            }
+        });
+        if let (Some(app), None) = (candidates.next(), candidates.next()) {
+            match matched.iter_mut().find(|(other, _)| other.uuid == app.uuid) {
+                Some((_, total)) => *total += count,
+                None => matched.push((app, count)),
+            }
+        }
+    }
+    let max = matched.iter().map(|(_, count)| *count).fold(0.0, f64::max);
+    if max <= 0.0 {
+        return Ok(Vec::new());
+    }
+    Ok(matched
+        .into_iter()
+        .map(|(app, count)| Record {
+            desktop_id: app.desktop_id.clone(),
+            name: app.name.clone(),
+            frecency: SEED_LAUNCHES * count / max,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_apps() -> Vec<App> {
+        let app = |name: &str, exec: &str, desktop_id: &str| {
+            let mut app = App::new(name.to_owned(), "icon".to_owned(), exec.to_owned());
+            app.desktop_id = desktop_id.to_owned();
+            app
+        };
+        vec![
+            app(
+                "Firefox",
+                "/usr/lib/firefox/firefox --new-window",
+                "firefox.desktop",
+            ),
+            app("GIMP", "env GTK_THEME=Adwaita gimp-2.10", "gimp.desktop"),
+            app(
+                "Slack",
+                "flatpak run com.slack.Slack",
+                "com.slack.Slack.desktop",
+            ),
+            app(
+                "Spotify",
+                "flatpak run com.spotify.Client",
+                "com.spotify.Client.desktop",
+            ),
+        ]
+    }
+
+    fn frecency(records: &[Record], name: &str) -> Option<f64> {
+        records
+            .iter()
+            .find(|record| record.name == name)
+            .map(|record| record.frecency)
+    }
+
+    #[test]
+    fn rofi() {
+        let text =
+            "10 firefox.desktop\n5 /usr/share/applications/gimp.desktop\n3 unknown.desktop\n";
+        let records = records(&test_apps(), Source::Rofi, text.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(frecency(&records, "Firefox"), Some(SEED_LAUNCHES));
+        assert_eq!(frecency(&records, "GIMP"), Some(SEED_LAUNCHES / 2.0));
+    }
+
+    #[test]
+    fn ulauncher() {
+        let text =
+            r#"{"/usr/share/applications/firefox.desktop": 4, "/x/com.slack.Slack.desktop": 8}"#;
+        let records = records(&test_apps(), Source::Ulauncher, text.as_bytes()).unwrap();
+        assert_eq!(frecency(&records, "Slack"), Some(SEED_LAUNCHES));
+        assert_eq!(frecency(&records, "Firefox"), Some(SEED_LAUNCHES / 2.0));
+    }
+
+    #[test]
+    fn shells() {
+        let bash =
+            "#1571234567\nfirefox\nsudo gimp-2.10 image.png\nls\nflatpak run com.slack.Slack\n";
+        let zsh = ": 1571234567:0;firefox\n: 1571234568:0;GTK_THEME=Adwaita gimp-2.10\nfirefox\n";
+        let fish =
+            "- cmd: gimp-2.10\n  when: 1571234567\n- cmd: /usr/bin/firefox\n  when: 1571234568\n";
+        for (source, text) in &[
+            (Source::Bash, bash),
+            (Source::Zsh, zsh),
+            (Source::Fish, fish),
+        ] {
+            let records = records(&test_apps(), *source, text.as_bytes()).unwrap();
+            // flatpak runs two apps so it can't be credited to either.
+            assert_eq!(records.len(), 2, "{:?}", source);
+            assert!(frecency(&records, "Firefox").is_some(), "{:?}", source);
+            assert!(frecency(&records, "GIMP").is_some(), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn zsh_metafied() {
+        // "ć" is c4 87, which zsh writes as c4 83 a7.
+        assert_eq!(unmetafy(b"\xc4\x83\xa7"), "ć".as_bytes());
+        let zsh = b": 1571234567:0;firefox \xc4\x83\xa7\n\xff\xfe\n: 1571234568:0;gimp-2.10\n";
+        let records = records(&test_apps(), Source::Zsh, zsh).unwrap();
+        assert_eq!(frecency(&records, "Firefox"), Some(SEED_LAUNCHES));
+        assert_eq!(frecency(&records, "GIMP"), Some(SEED_LAUNCHES));
+    }
+}
@@ -14,9 +14,13 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
+use crate::db::{FrecencyKind, Layout, MatcherKind};
+use crate::profile::Profile;
 use crate::DIRS;
 use failure::Error;
+use log::*;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::default::Default;
 use std::fs::create_dir;
 
@@ -27,6 +31,25 @@ pub struct Config {
     pub app_paths: Vec<String>,
     /// Name of the icon theme to pull icons from.
     pub icon_theme: Option<String>,
+    /// How much of the launch history to keep.
+    #[serde(default)]
+    pub history: HistoryConfig,
+    /// When unused scores are dropped from the apps database.
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// How apps are ranked.
+    #[serde(default)]
+    pub ranking: RankingConfig,
+    /// Search texts that launch an app, mapped to the app's desktop entry
+    /// file name, uuid or name.
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    /// Settings that differ in each profile, by profile name.
+    ///
+    /// These are merged into the settings above when the profile is used,
+    /// see `Config::load_profile`.
+    #[serde(default, skip_serializing)]
+    pub profiles: HashMap<String, config::Value>,
 }
 
 impl Default for Config {
@@ -34,13 +57,194 @@ impl Default for Config {
         Config {
             app_paths: vec!["/usr/share/applications".to_owned()],
             icon_theme: None,
+            history: HistoryConfig::default(),
+            retention: RetentionConfig::default(),
+            ranking: RankingConfig::default(),
+            aliases: HashMap::new(),
+            profiles: HashMap::new(),
         }
     }
 }
 
+/// Retention settings of the launch history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// The most launches to keep, the oldest are dropped first.
+    pub max_entries: usize,
+    /// Launches older than this many days are dropped.
+    pub max_age_days: u64,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        HistoryConfig {
+            max_entries: 10_000,
+            max_age_days: 365,
+        }
+    }
+}
+
+/// Retention settings of the usage kept in the apps database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Launch scores and learned queries that decayed below this are
+    /// dropped.
+    pub min_frecency: f64,
+    /// Everything known about the use of apps not launched for this many
+    /// days is dropped, including when they're usually launched.
+    pub forget_after_days: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            min_frecency: 0.001,
+            forget_after_days: 365,
+        }
+    }
+}
+
+/// Settings of the ranking algorithm.
+///
+/// Every app matching the search gets the score
+///
+/// ```text
+/// fuzzy_weight * fuzzy + frecency_weight * frecency + learned_weight * learned + bonus
+/// ```
+///
+/// where each term is normalized to between 0 and 1 over the matching apps:
+///
+/// * `fuzzy` is the match score of the name divided by the best one, using
+///   the best scoring of `matchers`.
+/// * `frecency` is how often and how recently the app was launched as
+///   computed by `frecency_model`, on a log scale where the most used app
+///   is 1.
+/// * `learned` is the launches from similar queries, on a log scale where
+///   the most launched app is 1.
+/// * `bonus` is `exact_bonus` if the name is the search text or `prefix_bonus`
+///   if the name starts with it, ignoring case.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankingConfig {
+    /// How app names are matched to the search.
+    ///
+    /// An app matches if any of these match it and gets the best score.
+    pub matchers: Vec<MatcherKind>,
+    /// Pairs of keyboard layouts the search may have been typed in by
+    /// mistake.
+    ///
+    /// The search is also matched as if typed in the other layout of each
+    /// pair, so "ашкуащч" finds Firefox with the pair `(Us, Ru)`.
+    pub layouts: Vec<(Layout, Layout)>,
+    /// The share of the match score taken off matches in another layout.
+    pub layout_penalty: f64,
+    /// If fewer apps than this match the search, apps whose name matches
+    /// but for a few typos are shown after them. 0 turns this off.
+    pub typo_fallback: usize,
+    /// Weight of how well the name matches the search.
+    pub fuzzy_weight: f64,
+    /// Weight of how often and how recently the app was launched.
+    pub frecency_weight: f64,
+    /// How launches add up to the frecency of an app.
+    pub frecency_model: FrecencyKind,
+    /// Weight of how often the app was launched from similar searches.
+    pub learned_weight: f64,
+    /// Added when the name is the search text.
+    pub exact_bonus: f64,
+    /// Added when the name starts with the search text.
+    pub prefix_bonus: f64,
+    /// Days for the weight of a launch to halve.
+    pub half_life_days: f64,
+    /// Rank apps higher at the times of day and days of the week they're
+    /// usually launched.
+    pub context: bool,
+    /// How strongly the time of use changes the ranking when `context` is on.
+    pub context_weight: f64,
+    /// How much the apps listed before anything is typed are ordered by how
+    /// recently rather than how often they were launched, from 0 to 1.
+    pub recent_weight: f64,
+}
+
+impl Default for RankingConfig {
+    fn default() -> Self {
+        RankingConfig {
+            matchers: vec![MatcherKind::Skim],
+            layouts: Vec::new(),
+            layout_penalty: 0.2,
+            typo_fallback: 3,
+            fuzzy_weight: 1.0,
+            frecency_weight: 1.0,
+            frecency_model: FrecencyKind::Exponential,
+            learned_weight: 1.0,
+            exact_bonus: 1.0,
+            prefix_bonus: 0.5,
+            half_life_days: 3.0,
+            context: false,
+            context_weight: 1.0,
+            recent_weight: 0.0,
+        }
+    }
+}
+
+impl RankingConfig {
+    /// These settings with every number in its valid range.
+    ///
+    /// Weights and bonuses can't be negative and shares are between 0 and
+    /// 1, so scores are never `NaN`. Values out of range are clamped and a
+    /// half life that isn't positive is reset to the default.
+    pub fn clamped(self) -> RankingConfig {
+        let default = RankingConfig::default();
+        RankingConfig {
+            layout_penalty: clamp("layout_penalty", self.layout_penalty, 0.0, 1.0),
+            fuzzy_weight: clamp("fuzzy_weight", self.fuzzy_weight, 0.0, f64::MAX),
+            frecency_weight: clamp("frecency_weight", self.frecency_weight, 0.0, f64::MAX),
+            learned_weight: clamp("learned_weight", self.learned_weight, 0.0, f64::MAX),
+            exact_bonus: clamp("exact_bonus", self.exact_bonus, 0.0, f64::MAX),
+            prefix_bonus: clamp("prefix_bonus", self.prefix_bonus, 0.0, f64::MAX),
+            half_life_days: if self.half_life_days > 0.0 && self.half_life_days.is_finite() {
+                self.half_life_days
+            } else {
+                warn!(
+                    "ranking.half_life_days must be positive, using {}",
+                    default.half_life_days
+                );
+                default.half_life_days
+            },
+            context_weight: clamp("context_weight", self.context_weight, 0.0, f64::MAX),
+            recent_weight: clamp("recent_weight", self.recent_weight, 0.0, 1.0),
+            ..self
+        }
+    }
+}
+
+/// `value` of the ranking setting `name` clamped between `min` and `max`.
+///
+/// `NaN` is taken as `min`.
+fn clamp(name: &str, value: f64, min: f64, max: f64) -> f64 {
+    let clamped = if value.is_nan() {
+        min
+    } else {
+        value.max(min).min(max)
+    };
+    if clamped != value {
+        warn!(
+            "ranking.{} must be between {} and {}, using {}",
+            name, min, max, clamped
+        );
+    }
+    clamped
+}
+
 impl Config {
     /// Load the app config.
     pub fn load() -> Result<Config, Error> {
+        Self::load_profile(&Profile::default())
+    }
+
+    /// Load the app config with the settings of `profile` merged in.
+    pub fn load_profile(profile: &Profile) -> Result<Config, Error> {
         let mut cfg = config::Config::default();
         let config_dir = DIRS.config_dir();
         let mut file_path = None;
@@ -66,6 +270,94 @@ impl Config {
             }
         };
         cfg.merge(config::File::with_name(file_path.to_str().unwrap()))?;
-        Ok(cfg.try_into()?)
+        apply_profile(&mut cfg, profile)?;
+        let mut config: Config = cfg.try_into()?;
+        config.ranking = config.ranking.clamped();
+        Ok(config)
+    }
+}
+
+/// Merge the settings under `profiles.<name>` of `cfg` into the top level.
+///
+/// Tables are merged key by key, so a profile only needs to list the
+/// settings it changes.
+fn apply_profile(cfg: &mut config::Config, profile: &Profile) -> Result<(), Error> {
+    let name = match profile.name() {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    let settings = match cfg.get_table("profiles") {
+        Ok(mut profiles) => profiles.remove(name),
+        Err(_) => None,
+    };
+    if let Some(settings) = settings {
+        for (key, value) in settings.into_table()? {
+            cfg.set(&key, value)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamped_ranking() {
+        let ranking = RankingConfig {
+            fuzzy_weight: f64::NAN,
+            context_weight: -2.0,
+            recent_weight: 3.0,
+            half_life_days: 0.0,
+            learned_weight: 2.5,
+            ..RankingConfig::default()
+        }
+        .clamped();
+        assert_eq!(ranking.fuzzy_weight, 0.0);
+        assert_eq!(ranking.context_weight, 0.0);
+        assert_eq!(ranking.recent_weight, 1.0);
+        assert_eq!(
+            ranking.half_life_days,
+            RankingConfig::default().half_life_days
+        );
+        assert_eq!(ranking.learned_weight, 2.5);
+    }
+
+    #[test]
+    fn profile_settings() {
+        let text = r#"{
+            app_paths: ["/usr/share/applications"]
+            ranking: {
+                half_life_days: 7
+                fuzzy_weight: 2
+            }
+            profiles: {
+                Work: {
+                    ranking: {
+                        half_life_days: 1
+                    }
+                    aliases: {
+                        mail: thunderbird
+                    }
+                }
+            }
+        }"#;
+        let load = |profile: &str| -> Config {
+            let mut cfg = config::Config::default();
+            cfg.merge(config::File::from_str(text, config::FileFormat::Hjson))
+                .unwrap();
+            apply_profile(&mut cfg, &profile.parse().unwrap()).unwrap();
+            cfg.try_into().unwrap()
+        };
+        let default = load("default");
+        assert_eq!(default.ranking.half_life_days, 7.0);
+        assert!(default.aliases.is_empty());
+        for profile in &["Work", "work"] {
+            let work = load(profile);
+            assert_eq!(work.ranking.half_life_days, 1.0);
+            assert_eq!(work.ranking.fuzzy_weight, 2.0);
+            assert_eq!(work.aliases["mail"], "thunderbird");
+        }
+        assert_eq!(load("home").ranking.half_life_days, 7.0);
     }
 }
@@ -14,18 +14,63 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
+/// Usage by time of day and weekday
+mod context;
+/// Unicode folding of names and searches
+mod fold;
+/// On-disk layout and migrations of the database file
+mod format;
+/// Ways of combining how often and how recently apps were launched
+mod frecency;
+/// Dropping usage that no longer counts
+mod gc;
+/// Precomputed data for fast searches
+mod index;
+/// Export and import of app usage
+pub mod interchange;
+/// Correction of searches typed in the wrong keyboard layout
+mod layout;
+/// Ways of matching app names to a search
+mod matcher;
+/// Per-app ranking settings made by the user
+mod overrides;
+/// Crash-safe reading and writing of the database file
+mod storage;
+/// Reverting the last launch
+mod undo;
+
 use log::*;
 use std::cmp::Ordering;
 
+pub use self::context::{Clock, SystemClock, UsageContext};
+pub use self::fold::{fold, Folded};
+pub use self::format::FormatError;
+pub use self::frecency::{
+    BucketedModel, ExponentialModel, FrecencyKind, FrecencyModel, Moment, RecencyModel,
+    MAX_LAUNCH_TIMES,
+};
+pub use self::gc::Collected;
+pub use self::index::{Ranked, ScoreBreakdown, SearchIndex};
+pub use self::layout::{transpose, Layout};
+pub use self::matcher::{
+    build_matcher, AcronymMatcher, BestOf, EveryWord, Matcher, MatcherKind, PrefixMatcher,
+    RegexMatcher, SkimMatcher, SubstringMatcher, TypoMatcher,
+};
+pub use self::overrides::Overrides;
+
+use self::index::Candidate;
+use self::undo::LastLaunch;
 use super::App;
+use crate::config::{RankingConfig, RetentionConfig};
 use failure::{Error, Fail};
-use fuzzy_matcher::skim::fuzzy_match;
-use rmp_serde as rmp;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::Write as _;
-use std::path::Path;
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::Arc;
 use std::time::SystemTime;
 
 /// An apps database.
@@ -36,53 +81,250 @@ pub struct AppsDB {
     /// The reference time used in the ranking calculations.
     reference_time: f64,
     /// The half life of the app launches
-    half_life: f32,
+    half_life: f64,
+    /// Queries that apps were launched from.
+    ///
+    /// Maps a query to the uuids of the apps launched after typing it and
+    /// how often, stored relative to `reference_time` like app scores.
+    #[serde(default)]
+    learned: HashMap<String, HashMap<String, f64>>,
+    /// Ranking settings the user made for single apps, by uuid.
+    ///
+    /// Apps with the default settings have no entry.
+    #[serde(default)]
+    overrides: HashMap<String, Overrides>,
+    /// Aliases made in the launcher, mapping a search text to an app id.
+    ///
+    /// See `App::has_id` for the forms of app id.
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+    /// The usage of the last launched app before the launch, see `launch`.
+    #[serde(default)]
+    last_launch: Option<LastLaunch>,
+    /// Where the current time is read from.
+    #[serde(skip, default = "system_clock")]
+    clock: Arc<dyn Clock>,
+    /// Settings of the ranking algorithm.
+    #[serde(skip)]
+    ranking: RankingConfig,
+    /// Aliases from the config file, in the same form as `aliases`.
+    #[serde(skip)]
+    config_aliases: HashMap<String, String>,
+    /// How app names are matched to searches, built from `ranking`.
+    #[serde(skip, default = "default_matcher")]
+    matcher: Arc<dyn Matcher>,
+    /// How launches add up to the frecency of an app, built from `ranking`.
+    #[serde(skip, default = "default_frecency_model")]
+    frecency_model: Arc<dyn FrecencyModel>,
+    /// When unused usage is dropped, see `gc`.
+    #[serde(skip)]
+    retention: RetentionConfig,
+    /// Set anew whenever how apps are matched or ranked changes, so a
+    /// `SearchIndex` built before knows to start over.
+    #[serde(skip, default = "next_generation")]
+    generation: u64,
+}
+
+fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+fn default_matcher() -> Arc<dyn Matcher> {
+    build_matcher(&RankingConfig::default().matchers)
+}
+
+fn default_frecency_model() -> Arc<dyn FrecencyModel> {
+    RankingConfig::default().frecency_model.model()
+}
+
+/// A generation no other database has had, see `AppsDB::generation`.
+fn next_generation() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, AtomicOrdering::Relaxed)
+}
+
+const SECS_PER_DAY: f64 = 60.0 * 60.0 * 24.0;
+
+/// Rebase scores once this many half lives have passed since the reference time.
+///
+/// Stored scores double every half life, so this bounds them to
+/// 2^REBASE_HALF_LIVES times the decayed frecency.
+const REBASE_HALF_LIVES: f64 = 16.0;
+
+/// The form queries are learned in.
+fn normalize_query(query: &str) -> String {
+    fold(query.trim()).to_lowercase()
 }
 
 #[allow(dead_code)]
 impl AppsDB {
     /// Create a new app.
     pub fn new(apps: Vec<App>) -> Self {
+        Self::with_clock(apps, system_clock())
+    }
+
+    /// Create a new app database that reads the time from `clock`.
+    pub fn with_clock(apps: Vec<App>, clock: Arc<dyn Clock>) -> Self {
         AppsDB {
             apps,
-            reference_time: current_time_secs(),
-            // Half life of 3 days
-            half_life: 60.0 * 60.0 * 24.0 * 3.0,
+            reference_time: clock.secs(),
+            half_life: RankingConfig::default().half_life_days * SECS_PER_DAY,
+            learned: HashMap::new(),
+            overrides: HashMap::new(),
+            aliases: HashMap::new(),
+            last_launch: None,
+            clock,
+            ranking: RankingConfig::default(),
+            config_aliases: HashMap::new(),
+            matcher: default_matcher(),
+            frecency_model: default_frecency_model(),
+            retention: RetentionConfig::default(),
+            generation: next_generation(),
+        }
+    }
+
+    /// Set where the current time is read from.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Set the settings of the ranking algorithm.
+    ///
+    /// Numbers out of their valid range are clamped, see
+    /// `RankingConfig::clamped`.
+    pub fn set_ranking(&mut self, ranking: RankingConfig) {
+        let ranking = ranking.clamped();
+        self.set_half_life(ranking.half_life_days * SECS_PER_DAY);
+        self.matcher = build_matcher(&ranking.matchers);
+        self.frecency_model = ranking.frecency_model.model();
+        self.ranking = ranking;
+        self.generation = next_generation();
+    }
+
+    /// Set the aliases from the config file.
+    ///
+    /// Aliases made with `set_alias` take precedence over these.
+    pub fn set_config_aliases(&mut self, aliases: HashMap<String, String>) {
+        self.config_aliases = aliases
+            .into_iter()
+            .map(|(alias, id)| (normalize_query(&alias), id))
+            .collect();
+    }
+
+    /// Change the half life of launches, keeping the current frecencies.
+    fn set_half_life(&mut self, half_life: f64) {
+        if half_life == self.half_life || half_life <= 0.0 {
+            return;
+        }
+        let elapsed = self.secs_elapsed();
+        let rescale = 2.0f64.powf(elapsed / half_life - elapsed / self.half_life);
+        for app in &mut self.apps {
+            app.score *= rescale;
+        }
+        for score in self.learned.values_mut().flat_map(HashMap::values_mut) {
+            *score *= rescale;
         }
+        self.half_life = half_life;
     }
 
     /// Load database file.
     ///
+    /// Files written in an older layout are migrated to the current one and
+    /// scores are rebased to the current time if needed.
+    /// If the file can't be read the backup made by the last `save` is
+    /// loaded instead.
+    ///
     /// # Arguments
     ///
     /// * `path` - Location of the database file
     pub fn load(path: impl AsRef<Path>) -> Result<AppsDB, Error> {
-        let path_str = path.as_ref().to_string_lossy().into_owned();
+        Self::load_with_clock(path, system_clock())
+    }
+
+    /// Load database file that reads the time from `clock`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Location of the database file
+    /// * `clock` - Where the current time is read from
+    pub fn load_with_clock(path: impl AsRef<Path>, clock: Arc<dyn Clock>) -> Result<AppsDB, Error> {
+        let path = path.as_ref();
+        let mut db = match Self::load_file(path) {
+            Ok(db) => db,
+            Err(e) => {
+                let backup = storage::backup_path(path);
+                // An older backup would be overwritten the next time the
+                // database is saved, losing the newer data.
+                if !backup.exists() || AppDBError::is_unsupported_version(&e) {
+                    return Err(e);
+                }
+                warn!("{}, loading backup {}", e, backup.display());
+                Self::load_file(&backup).map_err(|_| e)?
+            }
+        };
+        db.set_clock(clock);
+        db.rebase();
+        db.migrate_launches();
+        Ok(db)
+    }
+
+    /// Move an unreadable database file out of the way.
+    ///
+    /// Returns where the file was moved to, next to `path`.
+    pub fn set_aside(path: impl AsRef<Path>) -> Result<PathBuf, Error> {
+        let path = path.as_ref();
+        let now = current_time_secs() as u64;
         Ok(
-            rmp::from_read(File::open(&path).map_err(|e| AppDBError::FileOpen {
-                file: path_str.clone(),
-                err: e.into(),
-            })?)
-            .map_err(|e| AppDBError::ParseDB {
-                file: path_str.clone(),
+            storage::set_aside(path, now).map_err(|e| AppDBError::SetAside {
+                file: path.to_string_lossy().into_owned(),
                 err: e.into(),
             })?,
         )
     }
 
+    fn load_file(path: &Path) -> Result<AppsDB, Error> {
+        let path_str = path.to_string_lossy().into_owned();
+        let mut buf = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut buf))
+            .map_err(|e| AppDBError::FileOpen {
+                file: path_str.clone(),
+                err: e.into(),
+            })?;
+        Ok(format::decode(&buf).map_err(|e| AppDBError::ParseDB {
+            file: path_str.clone(),
+            err: e,
+        })?)
+    }
+
     /// Save database file.
     ///
+    /// The file is always written in the current layout, rebased to the
+    /// current time if needed and without the usage dropped by `gc`. The new
+    /// contents are written to a temporary file which then replaces the old
+    /// one, so a crash never leaves a half written database. The previous
+    /// file is kept as a backup and writers are serialized with an advisory
+    /// lock.
+    ///
     /// # Arguments
     ///
     /// * `path` - Location of the database file
     pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
-        let path_str = path.as_ref().to_string_lossy().into_owned();
-        let buf = rmp::to_vec(&self).expect("Failed to encode apps db");
-        let mut file = File::create(&path).map_err(|e| AppDBError::FileCreate {
+        let path = path.as_ref();
+        let path_str = path.to_string_lossy().into_owned();
+        let mut compacted = self.clone();
+        compacted.gc();
+        let buf = format::encode(&compacted).expect("Failed to encode apps db");
+        let _lock = storage::WriteLock::acquire(path).map_err(|e| AppDBError::Lock {
             file: path_str.clone(),
             err: e.into(),
         })?;
-        file.write_all(&buf).map_err(|e| AppDBError::FileWrite {
+        let (file, temp) = storage::create_temp(path).map_err(|e| AppDBError::FileCreate {
+            file: format!("{}.tmp", path_str),
+            err: e.into(),
+        })?;
+        storage::rotate_backup(path);
+        storage::commit_temp(file, &temp, path, &buf).map_err(|e| AppDBError::FileWrite {
             file: path_str.clone(),
             err: e.into(),
         })?;
@@ -91,31 +333,269 @@ impl AppsDB {
 
     /// Get the apps in rank order for a given search string.
     ///
-    /// This ranks the apps both by frecency score and fuzzy search.
+    /// This ranks the apps by how well their name matches, frecency score and how often
+    /// they were launched from similar queries before, combined as set by
+    /// `RankingConfig`. If contextual ranking is on, the frecency score is
+    /// raised for apps usually launched at the current hour and weekday.
+    /// Names and the search are folded before they're matched, so accents,
+    /// full-width forms and the case of letters outside ASCII don't matter.
+    /// The words of the search may match in any order, see `EveryWord`.
+    ///
+    /// If keyboard layouts are set in `RankingConfig::layouts`, apps matching
+    /// the search as if typed in another layout are added with their match
+    /// score lowered by `RankingConfig::layout_penalty`.
+    /// If fewer apps than `RankingConfig::typo_fallback` match, apps whose
+    /// name matches but for a few typos are added after them, see
+    /// `TypoMatcher`.
+    ///
+    /// Hidden apps are left out, pinned apps come before all others and the
+    /// rank score of boosted apps is multiplied by their boost. If the search
+    /// is an alias the app it names comes first, whether or not it matches.
+    ///
+    /// If the search is empty the most used apps are listed instead, see
+    /// `RankingConfig::recent_weight`.
+    ///
+    /// The `score` of the returned apps is their rank score. Use a
+    /// `SearchIndex` to also get the characters of each name that matched
+    /// and how the score was made up, see `ScoreBreakdown`.
     // TODO Remove num_items
     pub fn get_ranked_list(&self, search: &str, num_items: Option<usize>) -> Vec<App> {
-        let mut app_list = self
+        SearchIndex::new(self)
+            .search(self, search, num_items.unwrap_or(usize::MAX))
+            .into_iter()
+            .map(|ranked| {
+                let mut app = ranked.app.clone();
+                app.score = ranked.score;
+                app
+            })
+            .collect()
+    }
+
+    /// Rank the apps matching `search` as described in `get_ranked_list`
+    /// and return the best `limit`.
+    ///
+    /// `search` must be folded like the names of the candidates.
+    fn rank<'a>(
+        &'a self,
+        search: &str,
+        candidates: Vec<Candidate<'a, '_>>,
+        limit: usize,
+    ) -> Vec<Ranked<'a>> {
+        let ranking = &self.ranking;
+        let learned = self.learned_bonuses(search);
+        let now = self.clock.now();
+        let moment = self.moment();
+        let candidates: Vec<_> = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let overrides = self.overrides(&candidate.app.uuid);
+                if overrides.hidden {
+                    return None;
+                }
+                let app = candidate.app;
+                let mut frecency = self.frecency(app, &moment);
+                if ranking.context {
+                    frecency *=
+                        1.0 + ranking.context_weight * f64::from(app.context.affinity(&now));
+                }
+                let learned = *learned.get(app.uuid.as_str()).unwrap_or(&0.0);
+                Some((candidate, overrides, frecency, learned))
+            })
+            .collect();
+        let max_fuzzy = candidates.iter().map(|c| c.0.fuzzy).fold(0.0, f64::max);
+        let max_frecency = candidates.iter().map(|c| c.2).fold(0.0, f64::max);
+        let max_learned = candidates.iter().map(|c| c.3).fold(0.0, f64::max);
+        let lower_search = search.to_lowercase();
+        let mut ranked: Vec<_> = candidates
+            .into_iter()
+            .map(|(candidate, overrides, frecency, learned)| {
+                let transposed;
+                let lower_search = if candidate.search == search {
+                    &lower_search
+                } else {
+                    transposed = candidate.search.to_lowercase();
+                    &transposed
+                };
+                let bonus = if candidate.lower_name == lower_search {
+                    ranking.exact_bonus
+                } else if candidate.lower_name.starts_with(lower_search.as_str()) {
+                    ranking.prefix_bonus
+                } else {
+                    0.0
+                };
+                let fuzzy = if max_fuzzy > 0.0 {
+                    candidate.fuzzy / max_fuzzy
+                } else {
+                    0.0
+                };
+                let breakdown = ScoreBreakdown {
+                    fuzzy: candidate.fuzzy,
+                    fuzzy_term: ranking.fuzzy_weight * fuzzy,
+                    frecency,
+                    frecency_term: ranking.frecency_weight * log_share(frecency, max_frecency),
+                    learned,
+                    learned_term: ranking.learned_weight * log_share(learned, max_learned),
+                    bonus,
+                    boost: overrides.boost,
+                    pinned: overrides.pinned,
+                    alias: candidate.alias,
+                    typo: candidate.typo,
+                    other_layout: candidate.search != search,
+                    ..ScoreBreakdown::default()
+                };
+                let order = (candidate.alias, !candidate.typo, overrides.pinned);
+                (order, breakdown, candidate)
+            })
+            .collect();
+        ranked.sort_by(|(left_order, left, _), (right_order, right, _)| {
+            right_order
+                .cmp(left_order)
+                .then(right.total().total_cmp(&left.total()))
+        });
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(_, breakdown, candidate)| {
+                let matcher: &dyn Matcher = if candidate.typo {
+                    &TypoMatcher
+                } else {
+                    &*self.matcher
+                };
+                Ranked {
+                    app: candidate.app,
+                    score: breakdown.total(),
+                    indices: matcher
+                        .indices(&candidate.name.text, candidate.search)
+                        .map(|(_, indices)| candidate.name.original_indices(&indices))
+                        .unwrap_or_default(),
+                    breakdown,
+                }
+            })
+            .collect()
+    }
+
+    /// Rank the apps for an empty search and return the best `limit`.
+    ///
+    /// Apps are ranked by frecency, mixed with how recently they were last
+    /// launched as set by `RankingConfig::recent_weight`. Apps that were
+    /// never launched are left out unless they're pinned.
+    fn rank_unsearched(&self, limit: usize) -> Vec<Ranked<'_>> {
+        let ranking = &self.ranking;
+        let now = self.clock.now();
+        let moment = self.moment();
+        let secs = moment.secs;
+        let frecencies: Vec<_> = self
             .apps
             .iter()
-            .filter_map(|app| match fuzzy_match(&app.name, &search) {
-                Some(score) if score > 0 => {
-                    let mut app = app.clone();
-                    app.score += score as f32;
-                    Some(app)
+            .map(|app| {
+                let mut frecency = self.frecency(app, &moment);
+                if ranking.context {
+                    frecency *=
+                        1.0 + ranking.context_weight * f64::from(app.context.affinity(&now));
                 }
-                _ => None,
+                frecency
             })
-            .collect::<Vec<App>>();
-        app_list.sort_by(|left, right| right.score.partial_cmp(&left.score).unwrap());
-        if let Some(n) = num_items {
-            app_list = app_list.into_iter().take(n).collect();
-        }
-        app_list
+            .collect();
+        let max_frecency = frecencies.iter().cloned().fold(0.0, f64::max);
+        let recent_weight = ranking.recent_weight.clamp(0.0, 1.0);
+        let mut ranked: Vec<_> = self
+            .apps
+            .iter()
+            .zip(frecencies)
+            .filter_map(|(app, frecency)| {
+                let overrides = self.overrides(&app.uuid);
+                let recency = if app.last_used > 0.0 {
+                    2.0f64.powf(-(secs - app.last_used).max(0.0) / self.half_life)
+                } else {
+                    0.0
+                };
+                let breakdown = ScoreBreakdown {
+                    frecency,
+                    frecency_term: (1.0 - recent_weight) * log_share(frecency, max_frecency),
+                    recency,
+                    recency_term: recent_weight * recency,
+                    boost: overrides.boost,
+                    pinned: overrides.pinned,
+                    ..ScoreBreakdown::default()
+                };
+                let score = breakdown.total();
+                if overrides.hidden || !(score > 0.0 || overrides.pinned) {
+                    return None;
+                }
+                Some((
+                    overrides.pinned,
+                    Ranked {
+                        app,
+                        score,
+                        indices: Vec::new(),
+                        breakdown,
+                    },
+                ))
+            })
+            .collect();
+        ranked.sort_by(|(left_pinned, left), (right_pinned, right)| {
+            right_pinned
+                .cmp(left_pinned)
+                .then(right.score.total_cmp(&left.score))
+        });
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(_, ranked)| ranked)
+            .collect()
     }
 
     /// Increment to score for app `to_update` by 1 launch.
     pub fn update(&mut self, to_update: &App) {
         self.update_score(&to_update.uuid, 1.0);
+        let now = self.clock.now();
+        let secs = self.clock.secs();
+        if let Some(app) = self.apps.iter_mut().find(|app| app.uuid == to_update.uuid) {
+            app.context.record(&now);
+            app.last_used = secs;
+        }
+    }
+
+    /// Remember that `app` was launched after typing `query`.
+    pub fn learn(&mut self, query: &str, app: &App) {
+        let query = normalize_query(query);
+        if query.is_empty() {
+            return;
+        }
+        self.rebase();
+        let growth = 2.0f64.powf(self.secs_elapsed() / self.half_life);
+        *self
+            .learned
+            .entry(query)
+            .or_default()
+            .entry(app.uuid.clone())
+            .or_insert(0.0) += growth;
+    }
+
+    /// The decayed learned bonus of each app for `search`, by uuid.
+    ///
+    /// Launches from the same query count fully. Launches from a query that
+    /// extends `search`, or that `search` extends, count by the fraction of
+    /// the longer query the shorter one covers.
+    fn learned_bonuses(&self, search: &str) -> HashMap<&str, f64> {
+        let search = normalize_query(search);
+        let mut bonuses = HashMap::new();
+        if search.is_empty() {
+            return bonuses;
+        }
+        let decay = 2.0f64.powf(self.secs_elapsed() / self.half_life);
+        let search_len = search.chars().count();
+        for (query, apps) in &self.learned {
+            if !query.starts_with(&search) && !search.starts_with(query.as_str()) {
+                continue;
+            }
+            let query_len = query.chars().count();
+            let overlap = search_len.min(query_len) as f64 / search_len.max(query_len) as f64;
+            for (uuid, score) in apps {
+                *bonuses.entry(uuid.as_str()).or_insert(0.0) += overlap * score / decay;
+            }
+        }
+        bonuses
     }
 
     /// Sort the apps database by score.
@@ -127,9 +607,127 @@ impl AppsDB {
         });
     }
 
+    /// Find an app by its uuid or, ignoring case, its name.
+    pub fn find_app(&self, name_or_uuid: &str) -> Option<&App> {
+        let name = name_or_uuid.to_lowercase();
+        self.apps
+            .iter()
+            .find(|app| app.uuid == name_or_uuid)
+            .or_else(|| self.apps.iter().find(|app| app.name.to_lowercase() == name))
+    }
+
+    /// The ranking settings the user made for the app with `uuid`.
+    pub fn overrides(&self, uuid: &str) -> Overrides {
+        self.overrides.get(uuid).copied().unwrap_or_default()
+    }
+
+    /// The apps whose ranking settings were changed by the user.
+    pub fn overridden_apps(&self) -> impl Iterator<Item = (&App, Overrides)> {
+        self.apps
+            .iter()
+            .filter_map(move |app| self.overrides.get(&app.uuid).map(|o| (app, *o)))
+    }
+
+    /// Pin or unpin the app with `uuid`.
+    pub fn set_pinned(&mut self, uuid: &str, pinned: bool) {
+        self.edit_overrides(uuid, |overrides| overrides.pinned = pinned);
+    }
+
+    /// Set the factor the rank score of the app with `uuid` is multiplied by.
+    ///
+    /// A boost of 1 resets it. Boosts that aren't positive are ignored.
+    pub fn set_boost(&mut self, uuid: &str, boost: f64) {
+        if !(boost > 0.0 && boost.is_finite()) {
+            warn!("Ignoring invalid boost {}", boost);
+            return;
+        }
+        self.edit_overrides(uuid, |overrides| overrides.boost = boost);
+    }
+
+    /// Hide or show the app with `uuid` in search results.
+    pub fn set_hidden(&mut self, uuid: &str, hidden: bool) {
+        self.edit_overrides(uuid, |overrides| overrides.hidden = hidden);
+    }
+
+    fn edit_overrides(&mut self, uuid: &str, edit: impl FnOnce(&mut Overrides)) {
+        let mut overrides = self.overrides(uuid);
+        edit(&mut overrides);
+        if overrides.is_default() {
+            self.overrides.remove(uuid);
+        } else {
+            self.overrides.insert(uuid.to_owned(), overrides);
+        }
+    }
+
+    /// Make `alias` an alias of the app with `uuid`.
+    ///
+    /// The app is referred to by its desktop entry if it has one, so the
+    /// alias still works after the entry changes.
+    pub fn set_alias(&mut self, alias: &str, uuid: &str) {
+        let alias = normalize_query(alias);
+        if alias.is_empty() {
+            return;
+        }
+        if let Some(app) = self.apps.iter().find(|app| app.uuid == uuid) {
+            let id = if app.desktop_id.is_empty() {
+                app.uuid.clone()
+            } else {
+                app.desktop_id.clone()
+            };
+            self.aliases.insert(alias, id);
+        }
+    }
+
+    /// Remove the alias made with `set_alias`.
+    pub fn remove_alias(&mut self, alias: &str) {
+        self.aliases.remove(&normalize_query(alias));
+    }
+
+    /// The id of the app `search` is an alias of.
+    fn alias_target(&self, search: &str) -> Option<&str> {
+        let search = normalize_query(search);
+        self.aliases
+            .get(&search)
+            .or_else(|| self.config_aliases.get(&search))
+            .map(String::as_str)
+    }
+
     /// Seconds elapsed since the reference time.
-    fn secs_elapsed(&self) -> f32 {
-        (current_time_secs() - self.reference_time) as f32
+    fn secs_elapsed(&self) -> f64 {
+        self.clock.secs() - self.reference_time
+    }
+
+    /// If the reference time is far enough in the past to need a rebase.
+    fn needs_rebase(&self) -> bool {
+        self.secs_elapsed() >= REBASE_HALF_LIVES * self.half_life
+    }
+
+    /// Move the reference time to now if it's far enough in the past.
+    ///
+    /// Scores are stored relative to the reference time and grow
+    /// exponentially as it recedes, rebasing brings them back to their
+    /// decayed values so they never overflow.
+    fn rebase(&mut self) {
+        if !self.needs_rebase() {
+            return;
+        }
+        let elapsed = self.secs_elapsed();
+        let scale = 2.0f64.powf(-elapsed / self.half_life);
+        // Scores saved as f32 by older versions may have overflowed already.
+        let rebased = |score: f64| {
+            if score.is_finite() {
+                score * scale
+            } else {
+                f64::from(f32::MAX) * scale
+            }
+        };
+        for app in &mut self.apps {
+            app.score = rebased(app.score);
+        }
+        for score in self.learned.values_mut().flat_map(HashMap::values_mut) {
+            *score = rebased(*score);
+        }
+        self.reference_time += elapsed;
     }
 
     /// Update the score of an app.
@@ -138,13 +736,52 @@ impl AppsDB {
     ///
     /// * `uuid` - The uuid of the app to update.
     /// * `weight` - The amount to update to score by.
-    pub fn update_score(&mut self, uuid: &str, weight: f32) {
-        let elapsed = self.secs_elapsed();
-        self.apps
-            .iter_mut()
-            .find(|app| app.uuid == *uuid)
-            .unwrap()
-            .update_frecency(weight, elapsed, self.half_life);
+    pub fn update_score(&mut self, uuid: &str, weight: f64) {
+        self.rebase();
+        let now = self.moment();
+        let app = self.apps.iter_mut().find(|app| app.uuid == *uuid).unwrap();
+        app.update_frecency(weight, now.elapsed, now.half_life);
+        app.record_launch(weight, now.secs);
+    }
+
+    /// The current time for computing frecencies.
+    fn moment(&self) -> Moment {
+        Moment {
+            secs: self.clock.secs(),
+            elapsed: self.secs_elapsed(),
+            half_life: self.half_life,
+        }
+    }
+
+    /// The frecency of `app` at `now` with the model set in `RankingConfig`.
+    fn frecency(&self, app: &App, now: &Moment) -> f64 {
+        self.frecency_model.frecency(app, now)
+    }
+
+    /// Give apps launched before launch counts and times were kept a count
+    /// and time that match their score.
+    ///
+    /// The count is the decayed score. The time is the last launch if it's
+    /// known, or else as long ago as it takes one launch to decay to the
+    /// score, so every model ranks these apps about as they were.
+    fn migrate_launches(&mut self) {
+        let now = self.moment();
+        for app in &mut self.apps {
+            if app.launch_count > 0.0 || !app.launch_times.is_empty() {
+                continue;
+            }
+            let frecency = app.get_frecency(now.elapsed, now.half_life);
+            if frecency.is_nan() || frecency <= 0.0 {
+                continue;
+            }
+            let time = if app.last_used > 0.0 {
+                app.last_used
+            } else {
+                now.secs - now.half_life * frecency.recip().log2().max(0.0)
+            };
+            app.launch_count = frecency;
+            app.launch_times.push(time);
+        }
     }
 
     /// Merge the apps from a re-scan into the database.
@@ -155,33 +792,60 @@ impl AppsDB {
         let apps = std::mem::replace(&mut self.apps, Vec::new());
         self.apps = apps
             .into_iter()
-            .filter(|app| apps_to_merge.contains(app))
+            .filter_map(|mut app| {
+                let new = apps_to_merge.iter().find(|new| **new == app)?;
+                app.merge(new);
+                Some(app)
+            })
             .collect();
         apps_to_merge = apps_to_merge
             .into_iter()
             .filter(|app| !self.apps.contains(app))
             .collect();
         self.apps.extend(apps_to_merge);
+        let uuids: HashSet<_> = self.apps.iter().map(|app| app.uuid.clone()).collect();
+        for apps in self.learned.values_mut() {
+            apps.retain(|uuid, _| uuids.contains(uuid));
+        }
+        self.learned.retain(|_, apps| !apps.is_empty());
+        self.overrides.retain(|uuid, _| uuids.contains(uuid));
     }
 }
 
 #[allow(dead_code)]
 impl App {
-    fn get_frecency(&self, elapsed: f32, half_life: f32) -> f32 {
-        self.score / 2.0f32.powf(elapsed / half_life)
+    fn get_frecency(&self, elapsed: f64, half_life: f64) -> f64 {
+        self.score / 2.0f64.powf(elapsed / half_life)
     }
 
-    fn set_frecency(&mut self, new: f32, elapsed: f32, half_life: f32) {
-        self.score = new * 2.0f32.powf(elapsed / half_life);
+    fn set_frecency(&mut self, new: f64, elapsed: f64, half_life: f64) {
+        self.score = new * 2.0f64.powf(elapsed / half_life);
     }
 
-    fn update_frecency(&mut self, weight: f32, elapsed: f32, half_life: f32) {
+    fn update_frecency(&mut self, weight: f64, elapsed: f64, half_life: f64) {
         self.set_frecency(
             self.get_frecency(elapsed, half_life) + weight,
             elapsed,
             half_life,
         );
     }
+
+    /// Count a launch with `weight` at `secs`.
+    fn record_launch(&mut self, weight: f64, secs: f64) {
+        self.launch_count += weight;
+        self.launch_times.push(secs);
+        let excess = self.launch_times.len().saturating_sub(MAX_LAUNCH_TIMES);
+        self.launch_times.drain(..excess);
+    }
+}
+
+/// `value` on a log scale between 0 and 1, where `max` is 1.
+fn log_share(value: f64, max: f64) -> f64 {
+    if max <= 0.0 {
+        0.0
+    } else {
+        value.ln_1p() / max.ln_1p()
+    }
 }
 
 /// Return the current time in seconds as a float
@@ -206,11 +870,78 @@ pub enum AppDBError {
     FileWrite { file: String, err: Error },
     #[fail(display = "Couldn't parse apps database file {}: {}", file, err)]
     ParseDB { file: String, err: Error },
+    #[fail(display = "Failed to lock apps database file {}: {}", file, err)]
+    Lock { file: String, err: Error },
+    #[fail(display = "Failed to move aside apps database file {}: {}", file, err)]
+    SetAside { file: String, err: Error },
+}
+
+impl AppDBError {
+    /// Whether `err` is from loading a database written by a newer Poki
+    /// Launcher, which must not be overwritten.
+    pub fn is_unsupported_version(err: &Error) -> bool {
+        match err.downcast_ref::<AppDBError>() {
+            Some(AppDBError::ParseDB { err, .. }) => matches!(
+                err.downcast_ref::<FormatError>(),
+                Some(FormatError::UnsupportedVersion { .. })
+            ),
+            _ => false,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{DateTime, Duration, FixedOffset, TimeZone as _};
+    use std::sync::Mutex;
+    use tempdir::TempDir;
+
+    /// A clock that only moves when told to.
+    #[derive(Debug)]
+    pub struct TestClock(Mutex<DateTime<FixedOffset>>);
+
+    impl TestClock {
+        /// Start at midnight on Monday 2019-07-01 UTC.
+        pub fn new() -> Arc<TestClock> {
+            let start = FixedOffset::east_opt(0)
+                .unwrap()
+                .with_ymd_and_hms(2019, 7, 1, 0, 0, 0)
+                .unwrap();
+            Arc::new(TestClock(Mutex::new(start)))
+        }
+
+        fn advance(&self, duration: Duration) {
+            let mut now = self.0.lock().unwrap();
+            *now += duration;
+        }
+    }
+
+    impl Clock for TestClock {
+        fn now(&self) -> DateTime<FixedOffset> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    /// A database of apps with these names, each run by its name in lower
+    /// case, that reads the time from `clock`.
+    pub fn test_db(names: &[&str], clock: Arc<TestClock>) -> AppsDB {
+        let apps = names
+            .iter()
+            .map(|name| App::new(name.to_string(), "icon".to_owned(), name.to_lowercase()))
+            .collect();
+        AppsDB::with_clock(apps, clock)
+    }
+
+    /// Path of a database in a new temporary directory.
+    ///
+    /// The lock and backup files are made next to it. The directory is
+    /// removed when the returned `TempDir` is dropped, so keep it alive.
+    fn temp_db_path() -> (TempDir, PathBuf) {
+        let dir = TempDir::new("poki-test").unwrap();
+        let path = dir.path().join("apps.db");
+        (dir, path)
+    }
 
     #[test]
     fn merge_new_entries_identical() {
@@ -267,4 +998,749 @@ mod tests {
         apps_db.merge_new_entries(apps.clone());
         assert_eq!(apps, apps_db.apps);
     }
+
+    mod learn {
+        use super::*;
+
+        fn test_db() -> AppsDB {
+            super::test_db(&["Terminal", "Telegram"], TestClock::new())
+        }
+
+        #[test]
+        fn same_query() {
+            let mut apps_db = test_db();
+            let ranked = apps_db.get_ranked_list("te", None);
+            let second = ranked[1].clone();
+            apps_db.learn("te", &second);
+            assert_eq!(apps_db.get_ranked_list("te", None)[0], second);
+        }
+
+        #[test]
+        fn extended_query() {
+            let mut apps_db = test_db();
+            let second = apps_db.get_ranked_list("te", None)[1].clone();
+            apps_db.learn("Tel", &second);
+            apps_db.learn("tel", &second);
+            assert_eq!(apps_db.get_ranked_list("te", None)[0], second);
+            assert_eq!(apps_db.get_ranked_list("tele", None)[0], second);
+        }
+
+        #[test]
+        fn unrelated_query() {
+            let mut apps_db = test_db();
+            let ranked = apps_db.get_ranked_list("te", None);
+            apps_db.learn("xyz", &ranked[1]);
+            assert_eq!(apps_db.get_ranked_list("te", None), ranked);
+        }
+
+        #[test]
+        fn forgotten_on_removal() {
+            let mut apps_db = test_db();
+            let telegram = apps_db.apps[1].clone();
+            apps_db.learn("te", &telegram);
+            apps_db.merge_new_entries(vec![apps_db.apps[0].clone()]);
+            assert!(apps_db.learned.is_empty());
+        }
+    }
+
+    mod overrides {
+        use super::*;
+
+        fn test_db() -> AppsDB {
+            super::test_db(&["Terminal", "Telegram"], TestClock::new())
+        }
+
+        #[test]
+        fn hidden() {
+            let mut apps_db = test_db();
+            let first = apps_db.get_ranked_list("te", None)[0].clone();
+            apps_db.set_hidden(&first.uuid, true);
+            let ranked = apps_db.get_ranked_list("te", None);
+            assert_eq!(ranked.len(), 1);
+            assert_ne!(ranked[0], first);
+            apps_db.set_hidden(&first.uuid, false);
+            assert!(apps_db.overrides.is_empty());
+        }
+
+        #[test]
+        fn pinned_beats_learned() {
+            let mut apps_db = test_db();
+            let ranked = apps_db.get_ranked_list("te", None);
+            apps_db.learn("te", &ranked[0]);
+            apps_db.set_pinned(&ranked[1].uuid, true);
+            assert_eq!(apps_db.get_ranked_list("te", None)[0], ranked[1]);
+        }
+
+        #[test]
+        fn boosted() {
+            let mut apps_db = test_db();
+            let ranked = apps_db.get_ranked_list("te", None);
+            apps_db.set_boost(&ranked[1].uuid, 10.0);
+            apps_db.set_boost(&ranked[0].uuid, -1.0);
+            assert_eq!(apps_db.get_ranked_list("te", None)[0], ranked[1]);
+            assert_eq!(apps_db.overridden_apps().count(), 1);
+        }
+
+        #[test]
+        fn forgotten_on_removal() {
+            let mut apps_db = test_db();
+            let telegram = apps_db.find_app("telegram").unwrap().clone();
+            apps_db.set_pinned(&telegram.uuid, true);
+            apps_db.merge_new_entries(vec![apps_db.apps[0].clone()]);
+            assert!(apps_db.overrides.is_empty());
+        }
+    }
+
+    mod aliases {
+        use super::*;
+
+        fn test_db() -> AppsDB {
+            let mut apps_db = super::test_db(&["Firefox", "Files"], TestClock::new());
+            apps_db.apps[0].desktop_id = "firefox.desktop".to_owned();
+            apps_db.apps[1].desktop_id = "org.gnome.Nautilus.desktop".to_owned();
+            apps_db
+        }
+
+        #[test]
+        fn alias_ranks_first() {
+            let mut apps_db = test_db();
+            assert_eq!(apps_db.get_ranked_list("f", None)[0].name, "Firefox");
+            let files = apps_db.apps[1].uuid.clone();
+            apps_db.set_alias("F ", &files);
+            assert_eq!(apps_db.get_ranked_list("f", None)[0].name, "Files");
+            apps_db.remove_alias("f");
+            assert_eq!(apps_db.get_ranked_list("f", None)[0].name, "Firefox");
+        }
+
+        #[test]
+        fn config_alias_without_match() {
+            let mut apps_db = test_db();
+            assert!(apps_db.get_ranked_list("web", None).is_empty());
+            let mut aliases = HashMap::new();
+            aliases.insert("Web".to_owned(), "firefox".to_owned());
+            apps_db.set_config_aliases(aliases);
+            let ranked = apps_db.get_ranked_list("web", None);
+            assert_eq!(ranked.len(), 1);
+            assert_eq!(ranked[0].name, "Firefox");
+        }
+
+        #[test]
+        fn survives_rescan() {
+            let mut apps_db = test_db();
+            let firefox = apps_db.apps[0].uuid.clone();
+            apps_db.set_alias("ff", &firefox);
+            let mut updated = App::new(
+                "Firefox Web Browser".to_owned(),
+                "firefox".to_owned(),
+                "/usr/bin/firefox".to_owned(),
+            );
+            updated.desktop_id = "firefox.desktop".to_owned();
+            apps_db.merge_new_entries(vec![updated, apps_db.apps[1].clone()]);
+            assert_eq!(
+                apps_db.get_ranked_list("ff", None)[0].name,
+                "Firefox Web Browser"
+            );
+        }
+    }
+
+    mod empty_search {
+        use super::*;
+
+        /// Launch Terminal three times then Telegram once, a day later.
+        fn test_db(recent_weight: f64) -> AppsDB {
+            let clock = TestClock::new();
+            let mut apps_db = super::test_db(&["Terminal", "Telegram", "Files"], clock.clone());
+            apps_db.set_ranking(RankingConfig {
+                recent_weight,
+                ..RankingConfig::default()
+            });
+            let (terminal, telegram) = (apps_db.apps[0].clone(), apps_db.apps[1].clone());
+            for _ in 0..3 {
+                apps_db.update(&terminal);
+            }
+            clock.advance(Duration::days(1));
+            apps_db.update(&telegram);
+            apps_db
+        }
+
+        fn names(apps_db: &AppsDB) -> Vec<String> {
+            apps_db
+                .get_ranked_list("", None)
+                .into_iter()
+                .map(|app| app.name)
+                .collect()
+        }
+
+        #[test]
+        fn most_used_first() {
+            assert_eq!(names(&test_db(0.0)), vec!["Terminal", "Telegram"]);
+        }
+
+        #[test]
+        fn most_recent_first() {
+            assert_eq!(names(&test_db(1.0)), vec!["Telegram", "Terminal"]);
+        }
+
+        #[test]
+        fn pinned_and_hidden() {
+            let mut apps_db = test_db(0.0);
+            let (terminal, files) = (apps_db.apps[0].uuid.clone(), apps_db.apps[2].uuid.clone());
+            apps_db.set_pinned(&files, true);
+            apps_db.set_hidden(&terminal, true);
+            assert_eq!(names(&apps_db), vec!["Files", "Telegram"]);
+        }
+    }
+
+    mod unicode {
+        use super::*;
+
+        fn test_db() -> AppsDB {
+            super::test_db(
+                &["Réseau", "Телеграм", "日本語入力", "ｶﾒﾗ", "ＦＩＲＥＦＯＸ"],
+                TestClock::new(),
+            )
+        }
+
+        fn first(apps_db: &AppsDB, search: &str) -> Option<String> {
+            apps_db
+                .get_ranked_list(search, None)
+                .first()
+                .map(|app| app.name.clone())
+        }
+
+        #[test]
+        fn latin() {
+            let apps_db = test_db();
+            assert_eq!(first(&apps_db, "reseau").as_deref(), Some("Réseau"));
+            assert_eq!(first(&apps_db, "RÉSEAU").as_deref(), Some("Réseau"));
+            assert_eq!(first(&apps_db, "fire").as_deref(), Some("ＦＩＲＥＦＯＸ"));
+        }
+
+        #[test]
+        fn cyrillic() {
+            let apps_db = test_db();
+            assert_eq!(first(&apps_db, "телег").as_deref(), Some("Телеграм"));
+            assert_eq!(first(&apps_db, "ТЕЛЕГ").as_deref(), Some("Телеграм"));
+        }
+
+        #[test]
+        fn cjk() {
+            let apps_db = test_db();
+            assert_eq!(first(&apps_db, "日本").as_deref(), Some("日本語入力"));
+            assert_eq!(first(&apps_db, "カメラ").as_deref(), Some("ｶﾒﾗ"));
+        }
+
+        #[test]
+        fn indices_of_original_name() {
+            let apps_db = AppsDB::new(vec![App::new(
+                "Re\u{301}seau".to_owned(),
+                "icon".to_owned(),
+                "/bin/app".to_owned(),
+            )]);
+            let ranked = SearchIndex::new(&apps_db).search(&apps_db, "res", usize::MAX);
+            assert_eq!(ranked[0].indices, [0, 1, 3]);
+        }
+    }
+
+    mod context {
+        use super::*;
+
+        /// Launch mail every morning and a game every evening for a week,
+        /// then return the ranking for the next morning.
+        fn next_morning(context: bool) -> Vec<App> {
+            let clock = TestClock::new();
+            let mut apps_db = AppsDB::with_clock(
+                vec![
+                    App::new(
+                        "App Mail".to_owned(),
+                        "icon".to_owned(),
+                        "/bin/mail".to_owned(),
+                    ),
+                    App::new(
+                        "App Game".to_owned(),
+                        "icon".to_owned(),
+                        "/bin/game".to_owned(),
+                    ),
+                ],
+                clock.clone(),
+            );
+            apps_db.set_ranking(RankingConfig {
+                context,
+                ..RankingConfig::default()
+            });
+            let (mail, game) = (apps_db.apps[0].clone(), apps_db.apps[1].clone());
+            for _ in 0..5 {
+                clock.advance(Duration::hours(8));
+                apps_db.update(&mail);
+                clock.advance(Duration::hours(12));
+                apps_db.update(&game);
+                clock.advance(Duration::hours(4));
+            }
+            clock.advance(Duration::hours(8));
+            apps_db.get_ranked_list("app", None)
+        }
+
+        #[test]
+        fn favors_usual_time() {
+            assert_eq!(next_morning(true)[0].name, "App Mail");
+        }
+
+        #[test]
+        fn off_by_default() {
+            // The game was launched more recently so it has the higher frecency.
+            assert_eq!(next_morning(false)[0].name, "App Game");
+        }
+    }
+
+    mod ranking {
+        use super::*;
+
+        /// Load the ranking fixture as of four weeks after it was started.
+        fn fixture(ranking: RankingConfig) -> AppsDB {
+            let path = format!("{}/fixtures/ranking.db", env!("CARGO_MANIFEST_DIR"));
+            let mut apps_db = AppsDB::load(path).unwrap();
+            let clock = TestClock::new();
+            clock.advance(Duration::days(28));
+            apps_db.set_clock(clock);
+            apps_db.set_ranking(ranking);
+            apps_db
+        }
+
+        fn names(apps_db: &AppsDB, search: &str) -> Vec<String> {
+            apps_db
+                .get_ranked_list(search, None)
+                .into_iter()
+                .map(|app| app.name)
+                .collect()
+        }
+
+        #[test]
+        fn default_formula() {
+            let apps_db = fixture(RankingConfig::default());
+            assert_eq!(
+                names(&apps_db, "te"),
+                vec![
+                    "Telegram",
+                    "Terminal",
+                    "Text Editor",
+                    "Tetris",
+                    "LibreOffice Writer",
+                    "Thunderbird"
+                ]
+            );
+            assert_eq!(
+                names(&apps_db, "t"),
+                vec![
+                    "Telegram",
+                    "Terminal",
+                    "Text Editor",
+                    "Thunderbird",
+                    "Tetris"
+                ]
+            );
+            assert_eq!(names(&apps_db, "f"), vec!["Firefox", "Files"]);
+            assert_eq!(names(&apps_db, "code"), vec!["Visual Studio Code"]);
+            assert_eq!(names(&apps_db, "code visual"), vec!["Visual Studio Code"]);
+            assert_eq!(names(&apps_db, "editor text")[0], "Text Editor");
+        }
+
+        #[test]
+        fn bad_weights() {
+            let apps_db = fixture(RankingConfig {
+                context: true,
+                context_weight: -5.0,
+                frecency_weight: f64::NAN,
+                ..RankingConfig::default()
+            });
+            assert_eq!(names(&apps_db, "te")[0], "Telegram");
+            assert!(!names(&apps_db, "").is_empty());
+        }
+
+        #[test]
+        fn frecency_models() {
+            let clock = TestClock::new();
+            let apps = ["Alpha Old", "Alpha New"]
+                .iter()
+                .map(|name| App::new(name.to_string(), "icon".to_owned(), name.to_lowercase()))
+                .collect();
+            let mut apps_db = AppsDB::with_clock(apps, clock.clone());
+            let (old, new) = (apps_db.apps[0].clone(), apps_db.apps[1].clone());
+            for _ in 0..20 {
+                apps_db.update(&old);
+            }
+            clock.advance(Duration::days(60));
+            apps_db.update(&new);
+            let first = |apps_db: &mut AppsDB, frecency_model| {
+                apps_db.set_ranking(RankingConfig {
+                    frecency_model,
+                    ..RankingConfig::default()
+                });
+                names(apps_db, "alpha").remove(0)
+            };
+            assert_eq!(first(&mut apps_db, FrecencyKind::Exponential), "Alpha New");
+            // Twenty launches two months ago outweigh one today.
+            assert_eq!(first(&mut apps_db, FrecencyKind::Bucketed), "Alpha Old");
+            assert_eq!(first(&mut apps_db, FrecencyKind::Recency), "Alpha New");
+        }
+
+        #[test]
+        fn migrated_launches() {
+            let apps_db = fixture(RankingConfig {
+                frecency_model: FrecencyKind::Bucketed,
+                ..RankingConfig::default()
+            });
+            for app in &apps_db.apps {
+                let launched = app.score > 0.0;
+                assert_eq!(app.launch_times.len(), launched as usize, "{}", app.name);
+                assert_eq!(app.launch_count > 0.0, launched, "{}", app.name);
+            }
+            assert_eq!(names(&apps_db, "te")[0], "Telegram");
+            assert_eq!(names(&apps_db, "f"), vec!["Firefox", "Files"]);
+        }
+
+        #[test]
+        fn breakdown() {
+            let mut apps_db = fixture(RankingConfig::default());
+            let uuid = apps_db.find_app("Terminal").unwrap().uuid.clone();
+            apps_db.set_boost(&uuid, 2.0);
+            let ranked = SearchIndex::new(&apps_db).search(&apps_db, "te", usize::MAX);
+            for ranked in &ranked {
+                assert_eq!(ranked.score, ranked.breakdown.total());
+            }
+            let terminal = ranked.iter().find(|r| r.app.name == "Terminal").unwrap();
+            let breakdown = &terminal.breakdown;
+            assert!(breakdown.fuzzy > 0.0 && breakdown.fuzzy_term <= 1.0);
+            assert!(breakdown.frecency > 0.0);
+            assert_eq!(breakdown.bonus, RankingConfig::default().prefix_bonus);
+            assert_eq!(breakdown.boost, 2.0);
+            assert!(!breakdown.typo && !breakdown.alias && !breakdown.other_layout);
+            // The most used app has the largest frecency term.
+            let max_term = ranked
+                .iter()
+                .map(|r| r.breakdown.frecency_term)
+                .fold(0.0, f64::max);
+            assert_eq!(max_term, RankingConfig::default().frecency_weight);
+        }
+
+        #[test]
+        fn without_learning() {
+            let apps_db = fixture(RankingConfig {
+                learned_weight: 0.0,
+                ..RankingConfig::default()
+            });
+            assert_eq!(names(&apps_db, "te")[..2], ["Terminal", "Telegram"]);
+        }
+
+        #[test]
+        fn frecency_does_not_swamp_match() {
+            let mut apps_db = AppsDB::new(vec![
+                App::new(
+                    "Gedit".to_owned(),
+                    "icon".to_owned(),
+                    "/bin/gedit".to_owned(),
+                ),
+                App::new(
+                    "Gnome Disks Utility".to_owned(),
+                    "icon".to_owned(),
+                    "/bin/disks".to_owned(),
+                ),
+            ]);
+            let disks = apps_db.apps[1].clone();
+            for _ in 0..1000 {
+                apps_db.update(&disks);
+            }
+            assert_eq!(apps_db.get_ranked_list("gedit", None)[0].name, "Gedit");
+        }
+
+        #[test]
+        fn half_life_change_keeps_frecency() {
+            let mut apps_db = fixture(RankingConfig::default());
+            let elapsed = apps_db.secs_elapsed();
+            let before = apps_db.apps[2].get_frecency(elapsed, apps_db.half_life);
+            apps_db.set_ranking(RankingConfig {
+                half_life_days: 7.0,
+                ..RankingConfig::default()
+            });
+            let after = apps_db.apps[2].get_frecency(elapsed, apps_db.half_life);
+            assert!((before - after).abs() < before * 1e-4);
+        }
+    }
+
+    mod rebase {
+        use super::*;
+
+        fn test_db(clock: Arc<TestClock>) -> AppsDB {
+            super::test_db(&["App Daily", "App First Year"], clock)
+        }
+
+        #[test]
+        fn years_of_use() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            let (daily, first_year) = (apps_db.apps[0].clone(), apps_db.apps[1].clone());
+            for day in 0..365 * 5 {
+                apps_db.update(&daily);
+                if day < 365 {
+                    apps_db.update(&first_year);
+                    apps_db.learn("f", &first_year);
+                }
+                clock.advance(Duration::days(1));
+            }
+            let bound = 2.0f64.powf(REBASE_HALF_LIVES + 4.0);
+            for app in &apps_db.apps {
+                assert!(app.score.is_finite() && app.score < bound);
+            }
+            let frecency = apps_db.apps[0].get_frecency(apps_db.secs_elapsed(), apps_db.half_life);
+            // Daily launches with a 3 day half life converge to 1 / (1 - 2^(-1/3)),
+            // decayed by the day since the last one.
+            let daily_decay = 2.0f64.powf(-1.0 / 3.0);
+            assert!((frecency - daily_decay / (1.0 - daily_decay)).abs() < 1e-6);
+            let ranked = apps_db.get_ranked_list("app", None);
+            assert_eq!(ranked[0].name, "App Daily");
+            assert!(ranked[1].score.is_finite());
+        }
+
+        #[test]
+        fn on_save_and_load() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            let daily = apps_db.apps[0].clone();
+            apps_db.update(&daily);
+            clock.advance(Duration::days(365 * 3));
+            let (_dir, path) = temp_db_path();
+            apps_db.save(&path).unwrap();
+            let loaded = AppsDB::load_with_clock(&path, clock.clone()).unwrap();
+            assert_eq!(loaded.reference_time, clock.secs());
+            assert!(loaded.apps[0].score < 1.0);
+        }
+
+        #[test]
+        fn overflowed_score() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            apps_db.apps[0].score = f64::INFINITY;
+            clock.advance(Duration::days(365));
+            apps_db.rebase();
+            assert!(apps_db.apps[0].score.is_finite());
+            assert!(apps_db.apps[0].score > apps_db.apps[1].score);
+        }
+    }
+
+    mod gc {
+        use super::*;
+        use crate::config::RetentionConfig;
+
+        fn test_db(clock: Arc<TestClock>) -> AppsDB {
+            super::test_db(&["Firefox", "Terminal"], clock)
+        }
+
+        #[test]
+        fn drops_decayed_scores() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            let (firefox, terminal) = (apps_db.apps[0].clone(), apps_db.apps[1].clone());
+            apps_db.update(&firefox);
+            apps_db.learn("fire", &firefox);
+            // Ten half lives later the launch counts for less than 0.001.
+            clock.advance(Duration::days(31));
+            apps_db.update(&terminal);
+            apps_db.learn("term", &terminal);
+            assert_eq!(
+                apps_db.gc(),
+                Collected {
+                    scores: 1,
+                    learned: 1,
+                    ..Collected::default()
+                }
+            );
+            assert_eq!(apps_db.apps[0].score, 0.0);
+            assert!(apps_db.apps[1].score > 0.0);
+            assert_eq!(apps_db.learned.len(), 1);
+            assert!(apps_db.gc().is_empty());
+        }
+
+        #[test]
+        fn forgets_unused_apps() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            apps_db.set_retention(RetentionConfig {
+                min_frecency: 0.0,
+                forget_after_days: 100,
+            });
+            let firefox = apps_db.apps[0].clone();
+            apps_db.update(&firefox);
+            clock.advance(Duration::days(99));
+            assert!(apps_db.gc().is_empty());
+            clock.advance(Duration::days(2));
+            assert_eq!(apps_db.gc().forgotten, 1);
+            assert_eq!(apps_db.apps[0].score, 0.0);
+            assert_eq!(apps_db.apps[0].context, UsageContext::default());
+        }
+
+        #[test]
+        fn drops_aliases_of_removed_apps() {
+            let mut apps_db = test_db(TestClock::new());
+            let firefox = apps_db.apps[0].uuid.clone();
+            apps_db.set_alias("web", &firefox);
+            assert!(apps_db.gc().is_empty());
+            let terminal = apps_db.apps[1].clone();
+            apps_db.merge_new_entries(vec![terminal]);
+            assert_eq!(apps_db.gc().aliases, 1);
+            assert!(apps_db.aliases.is_empty());
+        }
+
+        #[test]
+        fn on_save() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            let firefox = apps_db.apps[0].clone();
+            apps_db.update(&firefox);
+            clock.advance(Duration::days(60));
+            let (_dir, path) = temp_db_path();
+            apps_db.save(&path).unwrap();
+            let loaded = AppsDB::load_with_clock(&path, clock.clone()).unwrap();
+            assert_eq!(loaded.apps[0].score, 0.0);
+        }
+
+        #[test]
+        fn on_save_with_retention() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            apps_db.set_retention(RetentionConfig {
+                min_frecency: 0.0,
+                forget_after_days: 365,
+            });
+            let firefox = apps_db.apps[0].clone();
+            apps_db.update(&firefox);
+            apps_db.learn("fire", &firefox);
+            clock.advance(Duration::days(60));
+            let (_dir, path) = temp_db_path();
+            apps_db.save(&path).unwrap();
+            let loaded = AppsDB::load_with_clock(&path, clock.clone()).unwrap();
+            assert!(loaded.apps[0].score > 0.0);
+            assert_eq!(loaded.learned.len(), 1);
+        }
+    }
+
+    mod undo {
+        use super::*;
+
+        fn test_db(clock: Arc<TestClock>) -> AppsDB {
+            super::test_db(&["Firefox", "Files"], clock)
+        }
+
+        #[test]
+        fn reverts_launch() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            let (firefox, files) = (apps_db.apps[0].clone(), apps_db.apps[1].clone());
+            apps_db.launch("fi", &firefox);
+            clock.advance(Duration::hours(1));
+            let before = apps_db.clone();
+            apps_db.launch("fi", &files);
+            assert_eq!(apps_db.get_ranked_list("fi", None)[0].name, "Files");
+            clock.advance(Duration::hours(1));
+            assert_eq!(apps_db.undo_launch().unwrap().name, "Files");
+            assert_eq!(apps_db.get_ranked_list("fi", None)[0].name, "Firefox");
+            let elapsed = apps_db.secs_elapsed();
+            let frecency = |db: &AppsDB, i: usize| db.apps[i].get_frecency(elapsed, db.half_life);
+            assert_eq!(frecency(&apps_db, 1), 0.0);
+            assert!((frecency(&apps_db, 0) - frecency(&before, 0)).abs() < 1e-9);
+            assert_eq!(apps_db.learned, before.learned);
+            assert_eq!(apps_db.apps[1].context, UsageContext::default());
+            assert_eq!(apps_db.apps[1].last_used, 0.0);
+            assert!(apps_db.undo_launch().is_none());
+        }
+
+        #[test]
+        fn keeps_earlier_launches() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            let firefox = apps_db.apps[0].clone();
+            apps_db.launch("fire", &firefox);
+            clock.advance(Duration::days(3));
+            apps_db.launch("fire", &firefox);
+            apps_db.undo_launch();
+            let frecency = apps_db.apps[0].get_frecency(apps_db.secs_elapsed(), apps_db.half_life);
+            assert!((frecency - 0.5).abs() < 1e-9);
+            let learned = apps_db.learned_bonuses("fire")[firefox.uuid.as_str()];
+            assert!((learned - 0.5).abs() < 1e-9);
+        }
+
+        #[test]
+        fn after_save_and_load() {
+            let clock = TestClock::new();
+            let mut apps_db = test_db(clock.clone());
+            let firefox = apps_db.apps[0].clone();
+            apps_db.launch("", &firefox);
+            let (_dir, path) = temp_db_path();
+            apps_db.save(&path).unwrap();
+            let mut loaded = AppsDB::load_with_clock(&path, clock.clone()).unwrap();
+            assert!(loaded.undo_launch().is_some());
+            assert_eq!(loaded.apps[0].score, 0.0);
+        }
+    }
+
+    mod save {
+        use super::*;
+        use std::fs::write;
+
+        fn test_db(name: &str) -> AppsDB {
+            super::test_db(&[name], TestClock::new())
+        }
+
+        #[test]
+        fn keeps_backup() {
+            let (_dir, path) = temp_db_path();
+            test_db("First").save(&path).unwrap();
+            test_db("Second").save(&path).unwrap();
+            assert!(!path.with_extension("db.tmp").exists());
+            assert_eq!(AppsDB::load(&path).unwrap().apps[0].name, "Second");
+            let backup = AppsDB::load(storage::backup_path(&path)).unwrap();
+            assert_eq!(backup.apps[0].name, "First");
+        }
+
+        #[test]
+        fn corrupt_file_loads_backup() {
+            let (_dir, path) = temp_db_path();
+            test_db("First").save(&path).unwrap();
+            test_db("Second").save(&path).unwrap();
+            write(&path, b"garbage").unwrap();
+            assert_eq!(AppsDB::load(&path).unwrap().apps[0].name, "First");
+            // Saving over the corrupt file must not replace the good backup.
+            test_db("Third").save(&path).unwrap();
+            let backup = AppsDB::load(storage::backup_path(&path)).unwrap();
+            assert_eq!(backup.apps[0].name, "First");
+        }
+
+        #[test]
+        fn newer_version_ignores_backup() {
+            let (_dir, path) = temp_db_path();
+            test_db("First").save(&path).unwrap();
+            test_db("Second").save(&path).unwrap();
+            let mut buf = format::MAGIC.to_vec();
+            buf.extend_from_slice(&(format::CURRENT_VERSION + 1).to_le_bytes());
+            write(&path, buf).unwrap();
+            let err = AppsDB::load(&path).unwrap_err();
+            assert!(AppDBError::is_unsupported_version(&err));
+        }
+
+        #[test]
+        fn set_aside_keeps_file() {
+            let (_dir, path) = temp_db_path();
+            write(&path, b"garbage").unwrap();
+            let aside = AppsDB::set_aside(&path).unwrap();
+            assert!(!path.exists());
+            assert_eq!(std::fs::read(&aside).unwrap(), b"garbage");
+            let name = aside.file_name().unwrap().to_string_lossy();
+            assert!(name.starts_with("apps.db.corrupt-"));
+        }
+
+        #[test]
+        fn corrupt_file_without_backup_fails() {
+            let (_dir, path) = temp_db_path();
+            write(&path, b"garbage").unwrap();
+            assert!(AppsDB::load(&path).is_err());
+        }
+    }
 }
@@ -0,0 +1,104 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use chrono::{DateTime, Datelike as _, FixedOffset, Local, Timelike as _};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// A source of the current time.
+///
+/// Ranking reads the time through this so tests can control it.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current time in the local time zone.
+    fn now(&self) -> DateTime<FixedOffset>;
+
+    /// The current time in seconds since the Unix epoch.
+    fn secs(&self) -> f64 {
+        self.now().timestamp_millis() as f64 / 1000.0
+    }
+}
+
+/// The system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        Local::now().into()
+    }
+}
+
+/// When an app is used, as launch counts by local time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageContext {
+    /// Launches in each hour of the day, starting at midnight.
+    hours: [f32; 24],
+    /// Launches on each day of the week, starting on Monday.
+    weekdays: [f32; 7],
+}
+
+impl UsageContext {
+    /// Count a launch at `time`.
+    pub fn record(&mut self, time: &DateTime<FixedOffset>) {
+        self.hours[time.hour() as usize] += 1.0;
+        self.weekdays[time.weekday().num_days_from_monday() as usize] += 1.0;
+    }
+
+    /// How much of the app's use falls at the hour and weekday of `time`.
+    ///
+    /// This is the mean of the share of launches made around this hour and
+    /// the share made on this weekday, so it's between 0 and 1. The hour is
+    /// smoothed with its neighbours so 8:59 and 9:01 count alike.
+    pub fn affinity(&self, time: &DateTime<FixedOffset>) -> f32 {
+        let total: f32 = self.weekdays.iter().sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let hour = time.hour() as usize;
+        let around_hour =
+            (self.hours[(hour + 23) % 24] + 2.0 * self.hours[hour] + self.hours[(hour + 1) % 24])
+                / 2.0;
+        let weekday = self.weekdays[time.weekday().num_days_from_monday() as usize];
+        ((around_hour / total).min(1.0) + weekday / total) / 2.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    fn at(day: u32, hour: u32) -> DateTime<FixedOffset> {
+        // 2019-07-01 was a Monday.
+        FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2019, 7, day, hour, 30, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn affinity_by_hour_and_weekday() {
+        let mut context = UsageContext::default();
+        assert_eq!(context.affinity(&at(1, 8)), 0.0);
+        for day in 1..=4 {
+            context.record(&at(day, 8));
+        }
+        assert_eq!(context.affinity(&at(1, 8)), (1.0 + 0.25) / 2.0);
+        assert!(context.affinity(&at(1, 9)) < context.affinity(&at(1, 8)));
+        assert!(context.affinity(&at(1, 20)) < context.affinity(&at(1, 9)));
+        assert!(context.affinity(&at(7, 20)) < context.affinity(&at(1, 20)));
+    }
+}
@@ -0,0 +1,119 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use caseless::Caseless as _;
+use std::iter;
+use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+
+/// Text in the form names and searches are matched in.
+///
+/// The text is decomposed to NFKD, which turns full-width and other
+/// compatibility characters into their plain forms, then combining marks
+/// are removed and characters outside ASCII are case folded. So "Réseau"
+/// becomes "Reseau" and "ＦＩＲＥＦＯＸ" becomes "FIREFOX". ASCII letters
+/// keep their case since the matchers ignore it themselves and the skim
+/// matcher scores word starts by it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Folded {
+    /// The folded text.
+    pub text: String,
+    /// For each character of `text`, the index of the character of the
+    /// original text it came from. Empty if the text was unchanged.
+    origins: Vec<usize>,
+}
+
+impl Folded {
+    /// Fold `text`.
+    pub fn new(text: &str) -> Folded {
+        if text.is_ascii() {
+            return Folded {
+                text: text.to_owned(),
+                origins: Vec::new(),
+            };
+        }
+        let mut folded = Folded::default();
+        for (i, c) in text.chars().enumerate() {
+            let mut push = |c: char| {
+                folded.text.push(c);
+                folded.origins.push(i);
+            };
+            if c.is_ascii() {
+                push(c);
+                continue;
+            }
+            decompose_compatible(c, |c| {
+                if is_combining_mark(c) {
+                    return;
+                }
+                if c.is_ascii() {
+                    push(c);
+                } else {
+                    iter::once(c).default_case_fold().for_each(&mut push);
+                }
+            });
+        }
+        folded
+    }
+
+    /// Map indices of characters of the folded text to the indices of the
+    /// characters of the original text they came from.
+    pub fn original_indices(&self, indices: &[usize]) -> Vec<usize> {
+        if self.origins.is_empty() {
+            return indices.to_vec();
+        }
+        let mut original: Vec<usize> = indices
+            .iter()
+            .filter_map(|i| self.origins.get(*i).copied())
+            .collect();
+        original.dedup();
+        original
+    }
+}
+
+/// Fold `text` as in `Folded`, keeping only the folded text.
+pub fn fold(text: &str) -> String {
+    Folded::new(text).text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latin() {
+        assert_eq!(fold("Réseau"), "Reseau");
+        // The same name written with a combining acute accent.
+        let combining = Folded::new("Re\u{301}seau");
+        assert_eq!(combining.text, "Reseau");
+        assert_eq!(combining.original_indices(&[1, 2]), [1, 3]);
+        assert_eq!(fold("Straße"), "Strasse");
+        assert_eq!(fold("ﬁle"), "file");
+    }
+
+    #[test]
+    fn cyrillic() {
+        assert_eq!(fold("ПРИВЕТ"), fold("привет"));
+        assert_eq!(fold("Ёлка"), "елка");
+    }
+
+    #[test]
+    fn cjk() {
+        assert_eq!(fold("ＦＩＲＥＦＯＸ"), "FIREFOX");
+        assert_eq!(fold("日本語入力"), "日本語入力");
+        // Half-width katakana become full-width.
+        assert_eq!(fold("ｶﾒﾗ"), "カメラ");
+    }
+}
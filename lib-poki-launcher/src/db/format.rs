@@ -0,0 +1,293 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{AppsDB, UsageContext};
+use crate::App;
+use failure::{Error, Fail};
+use rmp_serde as rmp;
+use std::collections::HashMap;
+use std::convert::TryInto as _;
+
+/// Magic bytes at the start of every versioned database file.
+pub const MAGIC: &[u8; 8] = b"POKIAPPS";
+/// Version of the database layout written by this build.
+pub const CURRENT_VERSION: u32 = 2;
+
+/// An error from reading the database file header.
+#[derive(Debug, Fail)]
+pub enum FormatError {
+    /// The file was written by a newer Poki Launcher than this one.
+    #[fail(
+        display = "Database version {} is newer than the supported version {}",
+        version, supported
+    )]
+    UnsupportedVersion { version: u32, supported: u32 },
+    /// The file ends before the version number.
+    #[fail(display = "Database header is truncated")]
+    TruncatedHeader,
+}
+
+/// Serialize the database with a header in the current layout.
+///
+/// The file is the magic bytes, the version as a little endian `u32`,
+/// then the database as MessagePack.
+///
+/// Structs are encoded positionally. A new field may be appended to the end
+/// of a struct with `#[serde(default)]` without changing the version, any
+/// other change to the layout needs a new version and a migration step.
+pub fn encode(db: &AppsDB) -> Result<Vec<u8>, Error> {
+    let payload = rmp::to_vec(db)?;
+    let mut buf = Vec::with_capacity(MAGIC.len() + 4 + payload.len());
+    buf.extend_from_slice(MAGIC);
+    buf.extend_from_slice(&CURRENT_VERSION.to_le_bytes());
+    buf.extend(payload);
+    Ok(buf)
+}
+
+/// Deserialize a database file of any known version.
+///
+/// Older layouts are migrated up to the current one.
+pub fn decode(buf: &[u8]) -> Result<AppsDB, Error> {
+    let (version, payload) = split_header(buf)?;
+    migrate(version, payload)
+}
+
+/// Split the file into its version and payload.
+fn split_header(buf: &[u8]) -> Result<(u32, &[u8]), Error> {
+    if !buf.starts_with(MAGIC) {
+        // Files written before the header existed are bare MessagePack.
+        return Ok((0, buf));
+    }
+    let rest = &buf[MAGIC.len()..];
+    if rest.len() < 4 {
+        return Err(FormatError::TruncatedHeader.into());
+    }
+    let (version, payload) = rest.split_at(4);
+    Ok((u32::from_le_bytes(version.try_into().unwrap()), payload))
+}
+
+/// Run the migration chain from `version` up to `CURRENT_VERSION`.
+///
+/// Each legacy layout is parsed with its own frozen types then upgraded
+/// one version at a time.
+fn migrate(version: u32, payload: &[u8]) -> Result<AppsDB, Error> {
+    match version {
+        0 => Ok(migrate_v1(
+            rmp::from_slice::<v0::AppsDB>(payload)?.upgrade(),
+        )),
+        1 => Ok(migrate_v1(rmp::from_slice(payload)?)),
+        CURRENT_VERSION => Ok(rmp::from_slice(payload)?),
+        _ => Err(FormatError::UnsupportedVersion {
+            version,
+            supported: CURRENT_VERSION,
+        }
+        .into()),
+    }
+}
+
+/// The layout written before the header was added.
+mod v0 {
+    use serde_derive::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    pub struct AppsDB {
+        pub apps: Vec<App>,
+        pub reference_time: f64,
+        pub half_life: f32,
+    }
+
+    #[derive(Debug, Deserialize)]
+    pub struct App {
+        pub name: String,
+        pub exec: String,
+        pub score: f32,
+        pub uuid: String,
+        pub icon: String,
+    }
+}
+
+impl v0::AppsDB {
+    /// Upgrade to version 1.
+    fn upgrade(self) -> v1::AppsDB {
+        let apps = self
+            .apps
+            .into_iter()
+            .map(|app| v1::App {
+                name: app.name,
+                exec: app.exec,
+                score: app.score,
+                uuid: app.uuid,
+                icon: app.icon,
+                context: UsageContext::default(),
+            })
+            .collect();
+        v1::AppsDB {
+            apps,
+            reference_time: self.reference_time,
+            half_life: self.half_life,
+            learned: HashMap::new(),
+        }
+    }
+}
+
+/// The first layout with a header, which stored scores as `f32`.
+mod v1 {
+    use super::UsageContext;
+    use serde_derive::Deserialize;
+    use std::collections::HashMap;
+
+    #[derive(Debug, Deserialize)]
+    pub struct AppsDB {
+        pub apps: Vec<App>,
+        pub reference_time: f64,
+        pub half_life: f32,
+        #[serde(default)]
+        pub learned: HashMap<String, HashMap<String, f32>>,
+    }
+
+    /// `UsageContext` has the same layout as in version 1.
+    #[derive(Debug, Deserialize)]
+    pub struct App {
+        pub name: String,
+        pub exec: String,
+        pub score: f32,
+        pub uuid: String,
+        pub icon: String,
+        #[serde(default)]
+        pub context: UsageContext,
+    }
+}
+
+/// Upgrade a version 1 database to version 2.
+fn migrate_v1(db: v1::AppsDB) -> AppsDB {
+    let apps = db
+        .apps
+        .into_iter()
+        .map(|app| App {
+            name: app.name,
+            exec: app.exec,
+            score: f64::from(app.score),
+            uuid: app.uuid,
+            icon: app.icon,
+            context: app.context,
+            ..App::default()
+        })
+        .collect();
+    let learned = db
+        .learned
+        .into_iter()
+        .map(|(query, apps)| {
+            let apps = apps
+                .into_iter()
+                .map(|(uuid, score)| (uuid, f64::from(score)))
+                .collect();
+            (query, apps)
+        })
+        .collect();
+    AppsDB {
+        reference_time: db.reference_time,
+        half_life: f64::from(db.half_life),
+        learned,
+        ..AppsDB::new(apps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    #[test]
+    fn round_trip() {
+        let db = AppsDB::new(vec![App::new(
+            "Test".to_owned(),
+            "icon".to_owned(),
+            "/bin/test".to_owned(),
+        )]);
+        let buf = encode(&db).unwrap();
+        assert!(buf.starts_with(MAGIC));
+        let decoded = decode(&buf).unwrap();
+        assert_eq!(db.apps, decoded.apps);
+        assert_eq!(db.apps[0].uuid, decoded.apps[0].uuid);
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let mut buf = MAGIC.to_vec();
+        buf.extend_from_slice(&(CURRENT_VERSION + 1).to_le_bytes());
+        assert!(decode(&buf).is_err());
+    }
+
+    #[test]
+    fn truncated_header_is_rejected() {
+        let mut buf = MAGIC.to_vec();
+        buf.push(1);
+        assert!(decode(&buf).is_err());
+    }
+
+    mod compat {
+        use super::*;
+
+        /// Check the contents shared by every fixture database.
+        fn check_fixture(db: &AppsDB) {
+            let names: Vec<_> = db.apps.iter().map(|app| app.name.as_str()).collect();
+            assert_eq!(names, vec!["Firefox", "Terminal", "Files"]);
+            assert_eq!(db.apps[0].uuid, "bb1444d9-c493-4591-b126-7c11a20614a9");
+            assert_eq!(db.apps[1].exec, "gnome-terminal");
+            assert_eq!(db.apps[2].icon, "system-file-manager");
+            assert!(db.apps[1].score > db.apps[0].score);
+            assert!(db.apps[0].score > db.apps[2].score);
+            assert_eq!(db.half_life, 60.0 * 60.0 * 24.0 * 3.0);
+        }
+
+        #[test]
+        fn load_v0() {
+            check_fixture(&AppsDB::load(fixture("apps-v0.db")).unwrap());
+        }
+
+        #[test]
+        fn load_v1() {
+            check_fixture(&AppsDB::load(fixture("apps-v1.db")).unwrap());
+        }
+
+        #[test]
+        fn load_v1_f32() {
+            let buf = std::fs::read(fixture("apps-v1-f32.db")).unwrap();
+            assert_eq!(split_header(&buf).unwrap().0, 1);
+            let db = decode(&buf).unwrap();
+            let names: Vec<_> = db.apps.iter().map(|app| app.name.as_str()).collect();
+            assert_eq!(names, vec!["Firefox", "Terminal", "Files"]);
+            assert!(db.apps[1].score > db.apps[0].score);
+            assert_eq!(db.apps[2].score, 0.0);
+            assert_ne!(db.apps[1].context, UsageContext::default());
+            assert_eq!(db.apps[2].context, UsageContext::default());
+            assert_eq!(db.half_life, 60.0 * 60.0 * 24.0 * 3.0);
+            // Three launches nine hours after the reference time.
+            let learned = db.learned["te"][&db.apps[1].uuid];
+            assert!((learned - 3.0 * 2f64.powf(9.0 / 72.0)).abs() < 1e-5);
+            assert!(db.learned["fi"].contains_key(&db.apps[0].uuid));
+
+            // Saved in the current layout it reads back the same.
+            let reloaded = decode(&encode(&db).unwrap()).unwrap();
+            assert_eq!(reloaded.apps, db.apps);
+            assert_eq!(reloaded.learned, db.learned);
+            assert_eq!(reloaded.reference_time, db.reference_time);
+        }
+    }
+}
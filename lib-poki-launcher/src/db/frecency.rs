@@ -0,0 +1,173 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::SECS_PER_DAY;
+use crate::App;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+
+/// The most launch times kept for each app.
+pub const MAX_LAUNCH_TIMES: usize = 10;
+
+/// Weight of a launch by its age in days for `BucketedModel`, for launches
+/// up to the age of each bucket.
+const BUCKETS: [(f64, f64); 4] = [(4.0, 1.0), (14.0, 0.7), (31.0, 0.5), (90.0, 0.3)];
+/// Weight of a launch older than every bucket for `BucketedModel`.
+const OLD_WEIGHT: f64 = 0.1;
+
+/// The time a frecency is computed at.
+#[derive(Debug, Clone, Copy)]
+pub struct Moment {
+    /// Seconds since the Unix epoch.
+    pub secs: f64,
+    /// Seconds since the reference time app scores are stored relative to.
+    pub elapsed: f64,
+    /// Seconds for the weight of a launch to halve.
+    pub half_life: f64,
+}
+
+/// A way of combining how often and how recently an app was launched.
+///
+/// Every model reads the same usage kept for each app: its exponentially
+/// decaying score, its launch count and the times of its latest launches.
+/// So the model can be changed at any time without losing usage. Learned
+/// queries always decay exponentially.
+pub trait FrecencyModel: fmt::Debug + Send + Sync {
+    /// The frecency of `app` at `now`, 0 if it was never launched.
+    fn frecency(&self, app: &App, now: &Moment) -> f64;
+}
+
+/// The frecency models that can be selected in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrecencyKind {
+    /// See `ExponentialModel`.
+    Exponential,
+    /// See `BucketedModel`.
+    Bucketed,
+    /// See `RecencyModel`.
+    Recency,
+}
+
+impl FrecencyKind {
+    /// The model of this kind.
+    pub fn model(self) -> Arc<dyn FrecencyModel> {
+        match self {
+            FrecencyKind::Exponential => Arc::new(ExponentialModel),
+            FrecencyKind::Bucketed => Arc::new(BucketedModel),
+            FrecencyKind::Recency => Arc::new(RecencyModel),
+        }
+    }
+}
+
+/// Every launch counts 1 and its weight halves every half life.
+#[derive(Debug, Clone, Copy)]
+pub struct ExponentialModel;
+
+impl FrecencyModel for ExponentialModel {
+    fn frecency(&self, app: &App, now: &Moment) -> f64 {
+        app.get_frecency(now.elapsed, now.half_life)
+    }
+}
+
+/// The launch count times the mean weight of the latest launches, as in
+/// Firefox.
+///
+/// Launches are weighted by age in steps, from 1 in the last 4 days down to
+/// 0.1 after 90 days, so old favourites fade slower than with
+/// `ExponentialModel` and never fade out.
+#[derive(Debug, Clone, Copy)]
+pub struct BucketedModel;
+
+impl FrecencyModel for BucketedModel {
+    fn frecency(&self, app: &App, now: &Moment) -> f64 {
+        if app.launch_times.is_empty() {
+            return 0.0;
+        }
+        let weights: f64 = app
+            .launch_times
+            .iter()
+            .map(|time| {
+                let age = (now.secs - time).max(0.0) / SECS_PER_DAY;
+                BUCKETS
+                    .iter()
+                    .find(|(days, _)| age <= *days)
+                    .map_or(OLD_WEIGHT, |(_, weight)| *weight)
+            })
+            .sum();
+        app.launch_count * weights / app.launch_times.len() as f64
+    }
+}
+
+/// Only how recently the app was launched, halving every half life.
+///
+/// The last launched app always comes first, however rarely it's used.
+#[derive(Debug, Clone, Copy)]
+pub struct RecencyModel;
+
+impl FrecencyModel for RecencyModel {
+    fn frecency(&self, app: &App, now: &Moment) -> f64 {
+        match app.launch_times.last() {
+            Some(time) => 2.0f64.powf(-(now.secs - time).max(0.0) / now.half_life),
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: f64 = 1000.0 * SECS_PER_DAY;
+
+    fn moment() -> Moment {
+        Moment {
+            secs: NOW,
+            elapsed: 0.0,
+            half_life: 3.0 * SECS_PER_DAY,
+        }
+    }
+
+    fn launched(days_ago: &[f64]) -> App {
+        let mut app = App::new("App".to_owned(), "icon".to_owned(), "app".to_owned());
+        for days in days_ago {
+            app.record_launch(1.0, NOW - days * SECS_PER_DAY);
+        }
+        app
+    }
+
+    #[test]
+    fn bucketed() {
+        let now = moment();
+        assert_eq!(BucketedModel.frecency(&launched(&[]), &now), 0.0);
+        assert_eq!(BucketedModel.frecency(&launched(&[1.0]), &now), 1.0);
+        assert_eq!(BucketedModel.frecency(&launched(&[20.0, 1.0]), &now), 1.5);
+        assert_eq!(BucketedModel.frecency(&launched(&[365.0]), &now), 0.1);
+        // The count keeps growing past the launch times kept.
+        let often = launched(&[5.0; 20]);
+        assert_eq!(often.launch_times.len(), MAX_LAUNCH_TIMES);
+        assert!((BucketedModel.frecency(&often, &now) - 14.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recency() {
+        let now = moment();
+        assert_eq!(RecencyModel.frecency(&launched(&[]), &now), 0.0);
+        assert_eq!(RecencyModel.frecency(&launched(&[9.0, 0.0]), &now), 1.0);
+        assert_eq!(RecencyModel.frecency(&launched(&[0.0, 6.0]), &now), 0.25);
+    }
+}
@@ -0,0 +1,88 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{AppsDB, UsageContext, SECS_PER_DAY};
+use crate::config::RetentionConfig;
+
+/// What `AppsDB::gc` dropped.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Collected {
+    /// Launch scores that decayed below `RetentionConfig::min_frecency`.
+    pub scores: usize,
+    /// Apps not launched for `RetentionConfig::forget_after_days` whose
+    /// usage was forgotten.
+    pub forgotten: usize,
+    /// Apps learned for a query whose score decayed below
+    /// `RetentionConfig::min_frecency`.
+    pub learned: usize,
+    /// Aliases of apps that aren't installed anymore.
+    pub aliases: usize,
+}
+
+impl Collected {
+    /// If nothing was dropped.
+    pub fn is_empty(&self) -> bool {
+        *self == Collected::default()
+    }
+}
+
+impl AppsDB {
+    /// Set when unused scores are dropped, see `gc`.
+    pub fn set_retention(&mut self, retention: RetentionConfig) {
+        self.retention = retention;
+    }
+
+    /// Drop usage that no longer affects the ranking.
+    ///
+    /// Launch scores and learned queries that decayed below
+    /// `RetentionConfig::min_frecency` are zeroed out, apps not launched for
+    /// `RetentionConfig::forget_after_days` lose their usage altogether and
+    /// aliases of apps that aren't installed are removed. This runs on every
+    /// `save`, so the database file only holds usage that still counts.
+    pub fn gc(&mut self) -> Collected {
+        self.rebase();
+        let mut collected = Collected::default();
+        let elapsed = self.secs_elapsed();
+        let decay = 2.0f64.powf(elapsed / self.half_life);
+        let min_score = self.retention.min_frecency * decay;
+        let cutoff = self.clock.secs() - self.retention.forget_after_days as f64 * SECS_PER_DAY;
+        for app in &mut self.apps {
+            if app.last_used > 0.0 && app.last_used < cutoff {
+                app.score = 0.0;
+                app.context = UsageContext::default();
+                app.last_used = 0.0;
+                app.launch_count = 0.0;
+                app.launch_times.clear();
+                collected.forgotten += 1;
+            } else if app.score > 0.0 && app.score < min_score {
+                app.score = 0.0;
+                collected.scores += 1;
+            }
+        }
+        for apps in self.learned.values_mut() {
+            let before = apps.len();
+            apps.retain(|_, score| *score >= min_score);
+            collected.learned += before - apps.len();
+        }
+        self.learned.retain(|_, apps| !apps.is_empty());
+        let apps = &self.apps;
+        let before = self.aliases.len();
+        self.aliases
+            .retain(|_, id| apps.iter().any(|app| app.has_id(id)));
+        collected.aliases = before - self.aliases.len();
+        collected
+    }
+}
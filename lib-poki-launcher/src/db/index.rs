@@ -0,0 +1,394 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::layout::transpose;
+use super::{fold, AppsDB, Folded, Matcher as _, TypoMatcher};
+use crate::App;
+use std::collections::HashMap;
+
+/// An app that matches a search and its rank score.
+#[derive(Debug, Clone)]
+pub struct Ranked<'a> {
+    /// The matching app.
+    pub app: &'a App,
+    /// How well the app fits the search, see `AppsDB::get_ranked_list`.
+    pub score: f64,
+    /// Indices of the characters of the app's name that match the search.
+    ///
+    /// Empty if the app is only shown because the search is its alias.
+    pub indices: Vec<usize>,
+    /// How `score` was made up.
+    pub breakdown: ScoreBreakdown,
+}
+
+/// The parts of the rank score of an app, see `RankingConfig`.
+///
+/// Each `*_term` is the part's share of the score, normalized over the
+/// ranked apps and multiplied by its weight.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScoreBreakdown {
+    /// The match score of the name, 0 for an empty search.
+    pub fuzzy: f64,
+    pub fuzzy_term: f64,
+    /// The decayed launch count, raised at the usual time of use if
+    /// contextual ranking is on.
+    pub frecency: f64,
+    pub frecency_term: f64,
+    /// The decayed launches from similar searches.
+    pub learned: f64,
+    pub learned_term: f64,
+    /// How recently the app was launched from 0 to 1, only used for an
+    /// empty search.
+    pub recency: f64,
+    pub recency_term: f64,
+    /// The bonus for a name that is or starts with the search.
+    pub bonus: f64,
+    /// The factor the user boosted the app by.
+    pub boost: f64,
+    /// If the user pinned the app.
+    pub pinned: bool,
+    /// If the search is an alias of the app.
+    pub alias: bool,
+    /// If the name only matches with typos.
+    pub typo: bool,
+    /// If the name only matches the search typed in another keyboard layout.
+    pub other_layout: bool,
+}
+
+impl ScoreBreakdown {
+    /// The rank score, the sum of the terms and the bonus times the boost.
+    pub fn total(&self) -> f64 {
+        (self.fuzzy_term + self.frecency_term + self.learned_term + self.recency_term + self.bonus)
+            * self.boost
+    }
+}
+
+/// An app matching a search, before it's ranked.
+pub struct Candidate<'a, 'i> {
+    pub app: &'a App,
+    /// The app's name folded for matching.
+    pub name: &'i Folded,
+    /// The folded name in lowercase.
+    pub lower_name: &'i str,
+    /// The match score of the name.
+    pub fuzzy: f64,
+    /// If the search is an alias of the app.
+    pub alias: bool,
+    /// If the name only matches with typos, see `TypoMatcher`.
+    pub typo: bool,
+    /// The folded search the name matched, which is the search typed in
+    /// another keyboard layout if the name only matched that.
+    pub search: &'i str,
+}
+
+/// The searchable text of an app, prepared ahead of the search.
+#[derive(Debug, Clone)]
+struct Entry {
+    uuid: String,
+    /// The name folded for matching.
+    name: Folded,
+    /// The folded name in lowercase.
+    lower_name: String,
+}
+
+/// An index of an apps database for searching as the user types.
+///
+/// Names are folded once when the index is built, see `Folded`, and the
+/// apps matching the last search are kept, so when the search is extended
+/// by another character only those are matched again. This is skipped for
+/// matchers where extending a search can match more apps, see
+/// `Matcher::narrows`.
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    entries: Vec<Entry>,
+    /// The generation of the database the index was built from.
+    generation: u64,
+    /// The last search and the positions of the apps whose name matches it.
+    last: Option<(String, Vec<usize>)>,
+}
+
+impl SearchIndex {
+    /// Build the index of `db`.
+    pub fn new(db: &AppsDB) -> SearchIndex {
+        SearchIndex {
+            entries: db
+                .apps
+                .iter()
+                .map(|app| {
+                    let name = Folded::new(&app.name);
+                    Entry {
+                        uuid: app.uuid.clone(),
+                        lower_name: name.text.to_lowercase(),
+                        name,
+                    }
+                })
+                .collect(),
+            generation: db.generation,
+            last: None,
+        }
+    }
+
+    /// If the apps in `db` are not the ones this index was built from, or
+    /// they're matched or ranked differently since.
+    fn is_stale(&self, db: &AppsDB) -> bool {
+        self.generation != db.generation
+            || self.entries.len() != db.apps.len()
+            || self
+                .entries
+                .iter()
+                .zip(&db.apps)
+                .any(|(entry, app)| entry.uuid != app.uuid)
+    }
+
+    /// Get the best `limit` apps of `db` in rank order for `search`.
+    ///
+    /// The index is rebuilt first if the apps in `db` or its ranking
+    /// settings changed since it was built. Apps are ranked as in `AppsDB::get_ranked_list`.
+    pub fn search<'a>(&mut self, db: &'a AppsDB, search: &str, limit: usize) -> Vec<Ranked<'a>> {
+        if self.is_stale(db) {
+            *self = SearchIndex::new(db);
+        }
+        if search.trim().is_empty() {
+            self.last = None;
+            return db.rank_unsearched(limit);
+        }
+        let mut transposed: Vec<String> = db
+            .ranking
+            .layouts
+            .iter()
+            .flat_map(|&(a, b)| vec![transpose(search, a, b), transpose(search, b, a)])
+            .map(|variant| fold(&variant))
+            .collect();
+        let search = &Folded::new(search).text;
+        transposed.retain(|variant| variant != search);
+        transposed.sort();
+        transposed.dedup();
+        // Every app matching a search also matches the searches it extends.
+        let positions: Vec<usize> = match self.last.take() {
+            Some((last, positions))
+                if db.matcher.narrows() && !last.is_empty() && search.starts_with(&last) =>
+            {
+                positions
+            }
+            _ => (0..self.entries.len()).collect(),
+        };
+        let entries = &self.entries;
+        let candidate = |i: usize, fuzzy: f64, alias: bool| Candidate {
+            app: &db.apps[i],
+            name: &entries[i].name,
+            lower_name: &entries[i].lower_name,
+            fuzzy,
+            alias,
+            typo: false,
+            search,
+        };
+        let mut matching = Vec::new();
+        let mut candidates = Vec::new();
+        for i in positions {
+            if let Some(fuzzy) = db.matcher.score(&entries[i].name.text, search) {
+                matching.push(i);
+                if fuzzy > 0 {
+                    candidates.push(candidate(i, fuzzy as f64, false));
+                }
+            }
+        }
+        if !transposed.is_empty() {
+            let mut found: HashMap<&str, usize> = candidates
+                .iter()
+                .enumerate()
+                .map(|(c, candidate)| (candidate.app.uuid.as_str(), c))
+                .collect();
+            let factor = 1.0 - db.ranking.layout_penalty;
+            for variant in &transposed {
+                for (i, entry) in entries.iter().enumerate() {
+                    let fuzzy = match db.matcher.score(&entry.name.text, variant) {
+                        Some(fuzzy) if fuzzy > 0 => fuzzy as f64 * factor,
+                        _ => continue,
+                    };
+                    match found.get(db.apps[i].uuid.as_str()) {
+                        Some(&c) if candidates[c].fuzzy >= fuzzy => {}
+                        Some(&c) => {
+                            candidates[c].fuzzy = fuzzy;
+                            candidates[c].search = variant;
+                        }
+                        None => {
+                            found.insert(&db.apps[i].uuid, candidates.len());
+                            candidates.push(Candidate {
+                                search: variant,
+                                ..candidate(i, fuzzy, false)
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        if candidates.len() < db.ranking.typo_fallback {
+            for (i, entry) in entries.iter().enumerate() {
+                if candidates.iter().any(|c| c.app.uuid == db.apps[i].uuid) {
+                    continue;
+                }
+                match TypoMatcher.score(&entry.name.text, search) {
+                    Some(fuzzy) if fuzzy > 0 => candidates.push(Candidate {
+                        typo: true,
+                        ..candidate(i, fuzzy as f64, false)
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        if let Some(alias) = db.alias_target(search) {
+            for (i, app) in db.apps.iter().enumerate() {
+                if !app.has_id(alias) {
+                    continue;
+                }
+                match candidates.iter_mut().find(|c| c.app.uuid == app.uuid) {
+                    Some(candidate) => candidate.alias = true,
+                    None => candidates.push(candidate(i, 0.0, true)),
+                }
+            }
+        }
+        let ranked = db.rank(search, candidates, limit);
+        self.last = Some((search.to_owned(), matching));
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RankingConfig;
+    use crate::db::tests::TestClock;
+    use crate::db::{Layout, MatcherKind};
+
+    fn test_db() -> AppsDB {
+        crate::db::tests::test_db(
+            &["Firefox", "Files", "Terminal", "Telegram", "Thunderbird"],
+            TestClock::new(),
+        )
+    }
+
+    fn names(ranked: &[Ranked]) -> Vec<String> {
+        ranked.iter().map(|r| r.app.name.clone()).collect()
+    }
+
+    #[test]
+    fn same_as_full_search() {
+        let apps_db = test_db();
+        let mut index = SearchIndex::new(&apps_db);
+        for search in &[
+            "t", "te", "tel", "te", "Te", "TeL", "f", "fi", "fil", "x", "xy", "",
+        ] {
+            let expected: Vec<_> = apps_db
+                .get_ranked_list(search, None)
+                .into_iter()
+                .map(|app| app.name)
+                .collect();
+            assert_eq!(
+                names(&index.search(&apps_db, search, usize::MAX)),
+                expected,
+                "{}",
+                search
+            );
+        }
+    }
+
+    #[test]
+    fn narrows_extended_search() {
+        let apps_db = test_db();
+        let mut index = SearchIndex::new(&apps_db);
+        index.search(&apps_db, "te", usize::MAX);
+        assert_eq!(index.last.as_ref().unwrap().1.len(), 3);
+        index.search(&apps_db, "tel", usize::MAX);
+        assert_eq!(index.last.as_ref().unwrap().1.len(), 2);
+    }
+
+    #[test]
+    fn match_indices() {
+        let apps_db = test_db();
+        let ranked = SearchIndex::new(&apps_db).search(&apps_db, "tbd", usize::MAX);
+        assert_eq!(ranked[0].app.name, "Thunderbird");
+        assert_eq!(ranked[0].indices, [0, 7, 10]);
+    }
+
+    #[test]
+    fn typos_after_matches() {
+        let apps_db = test_db();
+        let names = |search| names(&SearchIndex::new(&apps_db).search(&apps_db, search, 5));
+        assert_eq!(names("firfeox"), vec!["Firefox"]);
+        // "tel" only matches Telegram, so Terminal is added with a typo.
+        assert_eq!(names("tel"), vec!["Telegram", "Terminal"]);
+    }
+
+    #[test]
+    fn other_layouts() {
+        let mut apps_db = test_db();
+        apps_db.set_ranking(RankingConfig {
+            layouts: vec![(Layout::Us, Layout::Ru)],
+            ..RankingConfig::default()
+        });
+        let mut index = SearchIndex::new(&apps_db);
+        let ranked = index.search(&apps_db, "ашкуащч", usize::MAX);
+        assert_eq!(names(&ranked), vec!["Firefox"]);
+        assert_eq!(ranked[0].indices, (0..7).collect::<Vec<_>>());
+        // Matches in the layout typed in come before those in the other one.
+        let mut apps_db = AppsDB::new(
+            ["Тел", "Ntk"]
+                .iter()
+                .map(|name| App::new(name.to_string(), "icon".to_owned(), "app".to_owned()))
+                .collect(),
+        );
+        apps_db.set_ranking(RankingConfig {
+            layouts: vec![(Layout::Us, Layout::Ru)],
+            ..RankingConfig::default()
+        });
+        let ranked = index.search(&apps_db, "ntk", usize::MAX);
+        assert_eq!(names(&ranked), vec!["Ntk", "Тел"]);
+    }
+
+    #[test]
+    fn rebuilt_when_apps_change() {
+        let mut apps_db = test_db();
+        let mut index = SearchIndex::new(&apps_db);
+        assert!(index.search(&apps_db, "gi", usize::MAX).is_empty());
+        let gimp = App::new("GIMP".to_owned(), "icon".to_owned(), "gimp".to_owned());
+        let mut apps = apps_db.apps.clone();
+        apps.push(gimp);
+        apps_db.merge_new_entries(apps);
+        assert_eq!(
+            names(&index.search(&apps_db, "gim", usize::MAX)),
+            vec!["GIMP"]
+        );
+    }
+
+    #[test]
+    fn rebuilt_when_ranking_changes() {
+        let mut apps_db = test_db();
+        apps_db.set_ranking(RankingConfig {
+            matchers: vec![MatcherKind::Prefix],
+            ..RankingConfig::default()
+        });
+        let mut index = SearchIndex::new(&apps_db);
+        assert!(index.search(&apps_db, "i", usize::MAX).is_empty());
+        apps_db.set_ranking(RankingConfig {
+            matchers: vec![MatcherKind::Substring],
+            ..RankingConfig::default()
+        });
+        assert_eq!(
+            names(&index.search(&apps_db, "il", usize::MAX)),
+            vec!["Files"]
+        );
+    }
+}
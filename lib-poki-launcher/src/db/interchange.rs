@@ -0,0 +1,384 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::AppsDB;
+use crate::App;
+use failure::{Error, Fail};
+use serde_derive::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The usage of an app in a form that can be moved between machines.
+///
+/// Apps are identified by their desktop entry rather than their uuid,
+/// which is different on every machine.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// File name of the app's desktop entry.
+    pub desktop_id: String,
+    /// Display name of the app.
+    pub name: String,
+    /// The app's launch count decayed to the time of the export.
+    pub frecency: f64,
+}
+
+/// An error from reading or writing exported apps.
+#[derive(Debug, Fail)]
+pub enum InterchangeError {
+    #[fail(display = "Unknown format {}, expected json or csv", name)]
+    UnknownFormat { name: String },
+    #[fail(display = "Unknown strategy {}, expected replace, sum or max", name)]
+    UnknownStrategy { name: String },
+    #[fail(display = "CSV header is missing the {} column", name)]
+    MissingColumn { name: String },
+    #[fail(display = "Invalid record on line {}: {}", line, err)]
+    BadRecord { line: usize, err: Error },
+}
+
+/// A file format for exported apps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// A JSON array of records.
+    Json,
+    /// A header line then one record per line.
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = InterchangeError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(InterchangeError::UnknownFormat {
+                name: name.to_owned(),
+            }),
+        }
+    }
+}
+
+/// How an imported frecency is combined with the one already in the database.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Use the imported frecency.
+    Replace,
+    /// Add the imported frecency to the current one.
+    Sum,
+    /// Keep the larger of the two.
+    Max,
+}
+
+impl FromStr for Strategy {
+    type Err = InterchangeError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "replace" => Ok(Strategy::Replace),
+            "sum" => Ok(Strategy::Sum),
+            "max" => Ok(Strategy::Max),
+            _ => Err(InterchangeError::UnknownStrategy {
+                name: name.to_owned(),
+            }),
+        }
+    }
+}
+
+impl Strategy {
+    fn combine(self, current: f64, imported: f64) -> f64 {
+        match self {
+            Strategy::Replace => imported,
+            Strategy::Sum => current + imported,
+            Strategy::Max => current.max(imported),
+        }
+    }
+}
+
+const CSV_COLUMNS: [&str; 3] = ["desktop_id", "name", "frecency"];
+
+/// Write `records` in `format`.
+pub fn write(records: &[Record], format: Format) -> Result<String, Error> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(records)?),
+        Format::Csv => {
+            let mut out = CSV_COLUMNS.join(",");
+            out.push('\n');
+            for record in records {
+                out.push_str(&format!(
+                    "{},{},{}\n",
+                    csv_field(&record.desktop_id),
+                    csv_field(&record.name),
+                    record.frecency
+                ));
+            }
+            Ok(out)
+        }
+    }
+}
+
+/// Read records written in `format`.
+///
+/// CSV columns may be in any order and unknown columns are ignored.
+pub fn read(text: &str, format: Format) -> Result<Vec<Record>, Error> {
+    match format {
+        Format::Json => Ok(serde_json::from_str(text)?),
+        Format::Csv => {
+            let mut rows = csv_rows(text).into_iter();
+            let header = match rows.next() {
+                Some((_, header)) => header,
+                None => return Ok(Vec::new()),
+            };
+            let column = |name: &str| {
+                header
+                    .iter()
+                    .position(|column| column == name)
+                    .ok_or_else(|| InterchangeError::MissingColumn {
+                        name: name.to_owned(),
+                    })
+            };
+            let (desktop_id, name, frecency) =
+                (column("desktop_id")?, column("name")?, column("frecency")?);
+            rows.map(|(line, fields)| {
+                let field = |index: usize| fields.get(index).cloned().unwrap_or_default();
+                Ok(Record {
+                    desktop_id: field(desktop_id),
+                    name: field(name),
+                    frecency: field(frecency).trim().parse().map_err(
+                        |e: std::num::ParseFloatError| InterchangeError::BadRecord {
+                            line,
+                            err: e.into(),
+                        },
+                    )?,
+                })
+            })
+            .collect()
+        }
+    }
+}
+
+/// Quote `field` if it contains characters special to CSV.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Split CSV text into rows of fields, removing quotes.
+///
+/// Quoted fields may span lines. Each row comes with the line it starts on,
+/// counting from 1. Empty lines are skipped.
+fn csv_rows(text: &str) -> Vec<(usize, Vec<String>)> {
+    let mut rows = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = line;
+    let mut chars = text.chars().peekable();
+    loop {
+        let c = chars.next();
+        match c {
+            Some('"') if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            Some('"') => quoted = !quoted,
+            Some(',') if !quoted => fields.push(std::mem::take(&mut field)),
+            Some('\r') if !quoted && chars.peek() == Some(&'\n') => {}
+            Some('\n') if quoted => {
+                field.push('\n');
+                line += 1;
+            }
+            Some('\n') | None => {
+                if !fields.is_empty() || !field.is_empty() {
+                    fields.push(std::mem::take(&mut field));
+                    rows.push((start, std::mem::take(&mut fields)));
+                }
+                if c.is_none() {
+                    return rows;
+                }
+                line += 1;
+                start = line;
+            }
+            Some(c) => field.push(c),
+        }
+    }
+}
+
+impl App {
+    /// If `record` was exported from this app.
+    ///
+    /// Records are matched by desktop entry, or by name if either side
+    /// doesn't know its desktop entry.
+    fn matches_record(&self, record: &Record) -> bool {
+        if self.desktop_id.is_empty() || record.desktop_id.is_empty() {
+            self.name == record.name
+        } else {
+            self.desktop_id == record.desktop_id
+        }
+    }
+}
+
+/// What `AppsDB::import` did with the records.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Imported {
+    /// Records that matched an installed app.
+    pub matched: usize,
+    /// Records of apps that aren't installed.
+    pub not_installed: usize,
+    /// Records skipped for a negative or non-finite frecency.
+    pub invalid: usize,
+}
+
+impl AppsDB {
+    /// The usage of every app in the database.
+    pub fn export(&self) -> Vec<Record> {
+        let elapsed = self.secs_elapsed();
+        self.apps
+            .iter()
+            .map(|app| Record {
+                desktop_id: app.desktop_id.clone(),
+                name: app.name.clone(),
+                frecency: app.get_frecency(elapsed, self.half_life),
+            })
+            .collect()
+    }
+
+    /// Combine the usage in `records` with this database using `strategy`.
+    ///
+    /// Records of apps that aren't installed and records with an invalid
+    /// frecency are skipped. Returns how many records were of each kind.
+    pub fn import(&mut self, records: &[Record], strategy: Strategy) -> Imported {
+        self.rebase();
+        let elapsed = self.secs_elapsed();
+        let half_life = self.half_life;
+        let now = self.clock.secs();
+        let mut imported = Imported::default();
+        for record in records {
+            if !record.frecency.is_finite() || record.frecency < 0.0 {
+                imported.invalid += 1;
+                continue;
+            }
+            let app = match self.apps.iter_mut().find(|app| app.matches_record(record)) {
+                Some(app) => app,
+                None => {
+                    imported.not_installed += 1;
+                    continue;
+                }
+            };
+            let frecency = strategy.combine(app.get_frecency(elapsed, half_life), record.frecency);
+            app.set_frecency(frecency, elapsed, half_life);
+            app.launch_count = strategy.combine(app.launch_count, record.frecency);
+            if app.launch_times.is_empty() && app.launch_count > 0.0 {
+                app.launch_times.push(now);
+            }
+            imported.matched += 1;
+        }
+        imported
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::TestClock;
+
+    fn test_db() -> AppsDB {
+        let mut apps_db =
+            crate::db::tests::test_db(&["Firefox", "Terminal, \"Dev\""], TestClock::new());
+        apps_db.apps[0].desktop_id = "firefox.desktop".to_owned();
+        apps_db
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut apps_db = test_db();
+        let firefox = apps_db.apps[0].clone();
+        apps_db.update(&firefox);
+        let records = apps_db.export();
+        for format in &[Format::Json, Format::Csv] {
+            let text = write(&records, *format).unwrap();
+            assert_eq!(read(&text, *format).unwrap(), records);
+        }
+    }
+
+    #[test]
+    fn csv_quoted_newlines() {
+        let record = |name: &str| Record {
+            desktop_id: format!("{}.desktop", name.len()),
+            name: name.to_owned(),
+            frecency: 1.5,
+        };
+        let records = vec![record("Two\nLines"), record("Next"), record("\r\n\n")];
+        let text = write(&records, Format::Csv).unwrap();
+        assert_eq!(read(&text, Format::Csv).unwrap(), records);
+        let text = "desktop_id,name,frecency\r\n\r\na,\"b\r\nc\",1\r\nd,e,x\r\n";
+        match read(text, Format::Csv) {
+            Err(e) => assert_eq!(
+                e.to_string().split(':').next(),
+                Some("Invalid record on line 5")
+            ),
+            Ok(records) => panic!("read {:?}", records),
+        }
+        let records = read(&text.replace(",x", ",2"), Format::Csv).unwrap();
+        assert_eq!(records[0].name, "b\r\nc");
+        assert_eq!(records[1].name, "e");
+    }
+
+    #[test]
+    fn csv_columns_by_name() {
+        let text = "frecency,extra,desktop_id,name\n2.5,x,firefox.desktop,Firefox\n";
+        let records = read(text, Format::Csv).unwrap();
+        assert_eq!(records[0].desktop_id, "firefox.desktop");
+        assert_eq!(records[0].frecency, 2.5);
+        assert!(read("name,frecency\nFirefox,1\n", Format::Csv).is_err());
+        assert!(read("desktop_id,name,frecency\na,b,c\n", Format::Csv).is_err());
+    }
+
+    #[test]
+    fn strategies() {
+        let record = |frecency| Record {
+            desktop_id: "firefox.desktop".to_owned(),
+            name: "Firefox Nightly".to_owned(),
+            frecency,
+        };
+        let mut apps_db = test_db();
+        let firefox = apps_db.apps[0].clone();
+        apps_db.update(&firefox);
+        let frecency = |apps_db: &AppsDB| apps_db.export()[0].frecency;
+        assert_eq!(apps_db.import(&[record(0.5)], Strategy::Max).matched, 1);
+        assert_eq!(frecency(&apps_db), 1.0);
+        apps_db.import(&[record(2.0)], Strategy::Sum);
+        assert_eq!(frecency(&apps_db), 3.0);
+        apps_db.import(&[record(0.5)], Strategy::Replace);
+        assert_eq!(frecency(&apps_db), 0.5);
+        let unknown = Record {
+            desktop_id: "gimp.desktop".to_owned(),
+            ..record(1.0)
+        };
+        let invalid = record(f64::NAN);
+        let imported = apps_db.import(&[unknown, invalid], Strategy::Replace);
+        assert_eq!(
+            imported,
+            Imported {
+                matched: 0,
+                not_installed: 1,
+                invalid: 1,
+            }
+        );
+    }
+}
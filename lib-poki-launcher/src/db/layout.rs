@@ -0,0 +1,93 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use serde_derive::{Deserialize, Serialize};
+
+/// A keyboard layout.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// US QWERTY.
+    Us,
+    /// Russian ЙЦУКЕН.
+    Ru,
+    /// German QWERTZ.
+    De,
+}
+
+impl Layout {
+    /// The characters of the main keys, without and with shift.
+    ///
+    /// Keys are in the same order for every layout: the number row, the
+    /// three letter rows, with the key left of Enter after the top one.
+    fn keys(self) -> [&'static str; 2] {
+        match self {
+            Layout::Us => [
+                "`1234567890-=qwertyuiop[]\\asdfghjkl;'zxcvbnm,./",
+                "~!@#$%^&*()_+QWERTYUIOP{}|ASDFGHJKL:\"ZXCVBNM<>?",
+            ],
+            Layout::Ru => [
+                "ё1234567890-=йцукенгшщзхъ\\фывапролджэячсмитьбю.",
+                "Ё!\"№;%:?*()_+ЙЦУКЕНГШЩЗХЪ/ФЫВАПРОЛДЖЭЯЧСМИТЬБЮ,",
+            ],
+            Layout::De => [
+                "^1234567890ß´qwertzuiopü+#asdfghjklöäyxcvbnm,.-",
+                "°!\"§$%&/()=?`QWERTZUIOPÜ*'ASDFGHJKLÖÄYXCVBNM;:_",
+            ],
+        }
+    }
+}
+
+/// What `text` would have been if it was typed with the same keys in the
+/// `to` layout rather than the `from` layout.
+///
+/// Characters not on the main keys of `from` are kept.
+pub fn transpose(text: &str, from: Layout, to: Layout) -> String {
+    let (from, to) = (from.keys(), to.keys());
+    text.chars()
+        .map(|c| {
+            from.iter()
+                .zip(&to)
+                .find_map(|(from, to)| {
+                    let i = from.chars().position(|key| key == c)?;
+                    to.chars().nth(i)
+                })
+                .unwrap_or(c)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_keys() {
+        for layout in &[Layout::Us, Layout::Ru, Layout::De] {
+            for keys in &layout.keys() {
+                assert_eq!(keys.chars().count(), 47, "{:?}", layout);
+            }
+        }
+    }
+
+    #[test]
+    fn transposed() {
+        assert_eq!(transpose("ашкуащч", Layout::Ru, Layout::Us), "firefox");
+        assert_eq!(transpose("ghbdtn", Layout::Us, Layout::Ru), "привет");
+        assert_eq!(transpose("Ntktuhfv", Layout::Us, Layout::Ru), "Телеграм");
+        assert_eq!(transpose("zoom 2", Layout::De, Layout::Us), "yoom 2");
+    }
+}
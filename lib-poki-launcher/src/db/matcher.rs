@@ -0,0 +1,554 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use fuzzy_matcher::skim::{fuzzy_indices, fuzzy_match};
+use regex::{Regex, RegexBuilder};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Score per character of a search matched by `PrefixMatcher`.
+///
+/// The skim matcher scores a close match at about 16 per character, so
+/// the scores of the matchers can be compared.
+const PREFIX_SCORE: i64 = 16;
+/// Score per character of a search matched by `SubstringMatcher`.
+const SUBSTRING_SCORE: i64 = 12;
+/// Score per character of a search matched by `AcronymMatcher`.
+///
+/// Higher than a close fuzzy match, since matching every word is a
+/// strong sign the user typed the initials on purpose.
+const ACRONYM_SCORE: i64 = 24;
+/// Taken off the `AcronymMatcher` score for every initial not in the search.
+const ACRONYM_SKIP_PENALTY: i64 = 4;
+/// Score per character of the text matched by `RegexMatcher`.
+const REGEX_SCORE: i64 = 8;
+/// Most regexes `RegexMatcher` keeps compiled, enough for the words of a
+/// search and the whole of it.
+const REGEX_CACHE_SIZE: usize = 16;
+/// Score per character of a search word matched by `TypoMatcher`, less
+/// one for each typo.
+const TYPO_SCORE: i64 = 8;
+
+/// A way of matching app names to a search.
+pub trait Matcher: fmt::Debug + Send + Sync {
+    /// How well `name` matches `search`, or `None` if it doesn't.
+    ///
+    /// An empty search matches every name with a score of 0. Apps are only
+    /// shown for scores above 0.
+    fn score(&self, name: &str, search: &str) -> Option<i64> {
+        self.indices(name, search).map(|(score, _)| score)
+    }
+
+    /// The score as in `score` and the indices of the characters of `name`
+    /// that match `search`, in order.
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)>;
+
+    /// If every name matching a search also matches the searches that
+    /// extend it, so the search index can narrow its results.
+    fn narrows(&self) -> bool {
+        true
+    }
+}
+
+/// The matchers that can be selected in the config file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatcherKind {
+    /// See `SkimMatcher`.
+    Skim,
+    /// See `PrefixMatcher`.
+    Prefix,
+    /// See `AcronymMatcher`.
+    Acronym,
+    /// See `SubstringMatcher`.
+    Substring,
+    /// See `RegexMatcher`.
+    Regex,
+}
+
+impl MatcherKind {
+    fn matcher(self) -> Box<dyn Matcher> {
+        match self {
+            MatcherKind::Skim => Box::new(SkimMatcher),
+            MatcherKind::Prefix => Box::new(PrefixMatcher),
+            MatcherKind::Acronym => Box::new(AcronymMatcher),
+            MatcherKind::Substring => Box::new(SubstringMatcher),
+            MatcherKind::Regex => Box::new(RegexMatcher::default()),
+        }
+    }
+}
+
+/// Build the matcher for `kinds`, which gives a name the best score of any.
+///
+/// Uses the skim matcher if `kinds` is empty. Searches of several words
+/// are matched as in `EveryWord`.
+pub fn build_matcher(kinds: &[MatcherKind]) -> Arc<dyn Matcher> {
+    let matcher: Box<dyn Matcher> = match kinds {
+        [] => Box::new(SkimMatcher),
+        [kind] => kind.matcher(),
+        kinds => Box::new(BestOf(kinds.iter().map(|kind| kind.matcher()).collect())),
+    };
+    Arc::new(EveryWord(matcher))
+}
+
+/// Fuzzy matching as in the skim fuzzy finder.
+///
+/// The search characters must appear in the name in order, with runs
+/// of adjacent characters and word starts scoring higher. Case is ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct SkimMatcher;
+
+impl Matcher for SkimMatcher {
+    fn score(&self, name: &str, search: &str) -> Option<i64> {
+        fuzzy_match(name, search)
+    }
+
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)> {
+        fuzzy_indices(name, search)
+    }
+}
+
+/// Names starting with the search, ignoring case.
+#[derive(Debug, Clone, Copy)]
+pub struct PrefixMatcher;
+
+impl Matcher for PrefixMatcher {
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)> {
+        if name.to_lowercase().starts_with(&search.to_lowercase()) {
+            let len = search.chars().count();
+            Some((PREFIX_SCORE * len as i64, (0..len).collect()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Names containing the search, ignoring case.
+#[derive(Debug, Clone, Copy)]
+pub struct SubstringMatcher;
+
+impl Matcher for SubstringMatcher {
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)> {
+        let name = name.to_lowercase();
+        let start = name.find(&search.to_lowercase())?;
+        let start = name[..start].chars().count();
+        let len = search.chars().count();
+        Some((SUBSTRING_SCORE * len as i64, (start..start + len).collect()))
+    }
+}
+
+/// Names whose word initials contain the search in order, ignoring case.
+///
+/// Words are split at anything not a letter or digit and where a lower
+/// case letter is followed by an upper case one, so "gimp" matches
+/// "GNU Image Manipulation Program" and "vb" matches "VirtualBox".
+/// Spaces in the search are ignored. Names score lower for each initial
+/// the search leaves out.
+#[derive(Debug, Clone, Copy)]
+pub struct AcronymMatcher;
+
+impl Matcher for AcronymMatcher {
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)> {
+        let initials = initials(name);
+        let mut remaining = initials.iter();
+        let mut indices = Vec::new();
+        for c in search.chars().filter(|c| !c.is_whitespace()) {
+            let c = c.to_lowercase().next()?;
+            let (i, _) = remaining.find(|(_, initial)| *initial == c)?;
+            indices.push(*i);
+        }
+        if indices.is_empty() {
+            return Some((0, indices));
+        }
+        let matched = indices.len() as i64;
+        let skipped = initials.len() as i64 - matched;
+        let score = ACRONYM_SCORE * matched - ACRONYM_SKIP_PENALTY * skipped;
+        Some((score.max(1), indices))
+    }
+}
+
+/// The first letter of each word in `name` in lower case, with its
+/// character index.
+fn initials(name: &str) -> Vec<(usize, char)> {
+    let mut initials = Vec::new();
+    let mut prev: Option<char> = None;
+    for (i, c) in name.chars().enumerate() {
+        let starts_word = c.is_alphanumeric()
+            && match prev {
+                None => true,
+                Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase()),
+            };
+        if starts_word {
+            initials.extend(c.to_lowercase().next().map(|lower| (i, lower)));
+        }
+        prev = Some(c);
+    }
+    initials
+}
+
+/// Names matching the search as a regular expression, ignoring case.
+///
+/// Names score by the length of the first match. Searches that aren't
+/// valid regular expressions, such as while one is still being typed,
+/// match nothing.
+#[derive(Debug, Default)]
+pub struct RegexMatcher {
+    /// Recent searches and their compiled regexes, if they were valid.
+    ///
+    /// `EveryWord` matches each word of a search and the whole of it, so
+    /// several are used for every name.
+    cache: Mutex<HashMap<String, Option<Regex>>>,
+}
+
+impl Matcher for RegexMatcher {
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)> {
+        if search.is_empty() {
+            return Some((0, Vec::new()));
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if !cache.contains_key(search) {
+            if cache.len() >= REGEX_CACHE_SIZE {
+                cache.clear();
+            }
+            let regex = RegexBuilder::new(search)
+                .case_insensitive(true)
+                .build()
+                .ok();
+            cache.insert(search.to_owned(), regex);
+        }
+        let regex = cache[search].as_ref()?;
+        let found = regex.find(name)?;
+        let start = name[..found.start()].chars().count();
+        let len = found.as_str().chars().count();
+        Some((
+            (REGEX_SCORE * len as i64).max(1),
+            (start..start + len).collect(),
+        ))
+    }
+
+    /// Extending a regex can match more, such as adding `|` or `*`.
+    fn narrows(&self) -> bool {
+        false
+    }
+}
+
+/// Names with words that match the words of the search but for a few typos.
+///
+/// Typos are counted as the Damerau-Levenshtein distance between a search
+/// word and a name word or its start, so a letter added, dropped, changed
+/// or swapped with the next one is one typo. Search words of up to 2
+/// letters must match exactly, up to 5 may have 1 typo and longer ones 2.
+/// Case is ignored.
+///
+/// This is meant as a fallback when nothing else matches, it matches far
+/// more loosely than the other matchers.
+#[derive(Debug, Clone, Copy)]
+pub struct TypoMatcher;
+
+impl Matcher for TypoMatcher {
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)> {
+        let words = words(name);
+        let mut score = 0;
+        let mut indices = Vec::new();
+        for search_word in search.split_whitespace() {
+            let search_word: Vec<char> = search_word.to_lowercase().chars().collect();
+            let max_typos = match search_word.len() {
+                0..=2 => 0,
+                3..=5 => 1,
+                _ => 2,
+            };
+            let (start, len, typos) = words
+                .iter()
+                .map(|(start, word)| {
+                    let head = &word[..word.len().min(search_word.len())];
+                    let typos = typos(&search_word, word).min(typos(&search_word, head));
+                    (*start, head.len(), typos)
+                })
+                .filter(|(_, _, typos)| *typos <= max_typos)
+                .min_by_key(|(_, _, typos)| *typos)?;
+            score += TYPO_SCORE * (search_word.len() - typos) as i64;
+            indices.extend(start..start + len);
+        }
+        if indices.is_empty() {
+            return Some((0, indices));
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        Some((score.max(1), indices))
+    }
+
+    /// A longer search can be closer to a word than a shorter one.
+    fn narrows(&self) -> bool {
+        false
+    }
+}
+
+/// The words of `name` in lower case, with the character index they start at.
+fn words(name: &str) -> Vec<(usize, Vec<char>)> {
+    let mut words: Vec<(usize, Vec<char>)> = Vec::new();
+    let mut in_word = false;
+    for (i, c) in name.chars().enumerate() {
+        if !c.is_alphanumeric() {
+            in_word = false;
+            continue;
+        }
+        if !in_word {
+            words.push((i, Vec::new()));
+            in_word = true;
+        }
+        if let Some((_, word)) = words.last_mut() {
+            word.extend(c.to_lowercase());
+        }
+    }
+    words
+}
+
+/// The Damerau-Levenshtein distance between `a` and `b`, counting a swap
+/// of adjacent characters as one edit.
+fn typos(a: &[char], b: &[char]) -> usize {
+    // Rows of the distances between prefixes of `a` and `b`, two back,
+    // one back and the current one.
+    let mut before: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            row[j] = (prev[j] + 1).min(row[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut prev, row);
+    }
+    prev[b.len()]
+}
+
+/// Several matchers, a name gets the best score of any that match it.
+#[derive(Debug)]
+pub struct BestOf(pub Vec<Box<dyn Matcher>>);
+
+impl Matcher for BestOf {
+    fn score(&self, name: &str, search: &str) -> Option<i64> {
+        self.0
+            .iter()
+            .filter_map(|matcher| matcher.score(name, search))
+            .max()
+    }
+
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)> {
+        self.0
+            .iter()
+            .filter_map(|matcher| matcher.indices(name, search))
+            .max_by_key(|(score, _)| *score)
+    }
+
+    fn narrows(&self) -> bool {
+        self.0.iter().all(|matcher| matcher.narrows())
+    }
+}
+
+/// Searches of several words, matching names that match every word in
+/// any order.
+///
+/// A name scores the sum of the scores of the words, or the score of the
+/// whole search if that's higher, so "code visual" matches
+/// "Visual Studio Code" while "web browser" still scores as one run of
+/// characters in "Firefox Web Browser". Spaces around a single word are
+/// ignored.
+#[derive(Debug)]
+pub struct EveryWord(pub Box<dyn Matcher>);
+
+impl Matcher for EveryWord {
+    fn score(&self, name: &str, search: &str) -> Option<i64> {
+        let mut words = search.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => self.0.score(name, search),
+            (Some(word), None) => self.0.score(name, word),
+            _ => {
+                let every: Option<i64> = search
+                    .split_whitespace()
+                    .map(|word| self.0.score(name, word))
+                    .sum();
+                self.0.score(name, search).max(every)
+            }
+        }
+    }
+
+    fn indices(&self, name: &str, search: &str) -> Option<(i64, Vec<usize>)> {
+        let mut words = search.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => self.0.indices(name, search),
+            (Some(word), None) => self.0.indices(name, word),
+            _ => {
+                let mut score = 0;
+                let mut indices = Vec::new();
+                for word in search.split_whitespace() {
+                    let (word_score, word_indices) = self.0.indices(name, word)?;
+                    score += word_score;
+                    indices.extend(word_indices);
+                }
+                indices.sort_unstable();
+                indices.dedup();
+                match self.0.indices(name, search) {
+                    Some(whole) if whole.0 >= score => Some(whole),
+                    _ => Some((score, indices)),
+                }
+            }
+        }
+    }
+
+    fn narrows(&self) -> bool {
+        self.0.narrows()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best<'a>(matcher: &dyn Matcher, names: &[&'a str], search: &str) -> Option<&'a str> {
+        names
+            .iter()
+            .filter_map(|name| Some((*name, matcher.score(name, search)?)))
+            .filter(|(_, score)| *score > 0)
+            .max_by_key(|(_, score)| *score)
+            .map(|(name, _)| name)
+    }
+
+    #[test]
+    fn acronyms() {
+        let names = [
+            "GNU Image Manipulation Program",
+            "Gimmick Player",
+            "Visual Studio Code",
+            "VirtualBox Screenshot Capture",
+        ];
+        let matcher = build_matcher(&[MatcherKind::Skim, MatcherKind::Acronym]);
+        assert_eq!(
+            best(&*matcher, &names, "gimp"),
+            Some("GNU Image Manipulation Program")
+        );
+        assert_eq!(best(&*matcher, &names, "vsc"), Some("Visual Studio Code"));
+        assert_eq!(
+            best(&*matcher, &names, "vbs"),
+            Some("VirtualBox Screenshot Capture")
+        );
+        assert_eq!(AcronymMatcher.score("Gimmick Player", "gimp"), None);
+    }
+
+    #[test]
+    fn prefix_and_substring() {
+        assert!(PrefixMatcher.score("Firefox", "FIRE").is_some());
+        assert_eq!(PrefixMatcher.score("Firefox", "fox"), None);
+        assert!(SubstringMatcher.score("Firefox", "FOX").is_some());
+        assert_eq!(SubstringMatcher.score("Firefox", "ffx"), None);
+    }
+
+    #[test]
+    fn regex() {
+        let matcher = RegexMatcher::default();
+        assert!(matcher
+            .score("LibreOffice Writer", "^libre.*writer$")
+            .is_some());
+        assert_eq!(matcher.score("LibreOffice Calc", "^libre.*writer$"), None);
+        assert_eq!(matcher.score("LibreOffice Writer", "libre("), None);
+        assert!(!build_matcher(&[MatcherKind::Skim, MatcherKind::Regex]).narrows());
+    }
+
+    #[test]
+    fn regex_cache_keeps_every_word() {
+        let matcher = RegexMatcher::default();
+        // The searches `EveryWord` matches each name against in turn.
+        for name in &["Firefox Web Browser", "Files", "Terminal"] {
+            for search in &["fox", "web", "fox web"] {
+                matcher.score(name, search);
+            }
+        }
+        assert_eq!(matcher.cache.lock().unwrap().len(), 3);
+        assert!(matcher.score("Firefox Web Browser", "fox web").is_some());
+    }
+
+    #[test]
+    fn best_score_wins() {
+        let matcher = build_matcher(&[MatcherKind::Prefix, MatcherKind::Substring]);
+        assert_eq!(
+            matcher.score("Firefox", "fire"),
+            PrefixMatcher.score("Firefox", "fire")
+        );
+        assert_eq!(
+            matcher.score("Firefox", "fox"),
+            SubstringMatcher.score("Firefox", "fox")
+        );
+        assert_eq!(matcher.score("Firefox", "ffx"), None);
+    }
+
+    #[test]
+    fn typo_matches() {
+        assert!(TypoMatcher
+            .score("Firefox Web Browser", "firfeox")
+            .is_some());
+        assert!(TypoMatcher
+            .score("Firefox Web Browser", "brwoser fierfox")
+            .is_some());
+        assert!(TypoMatcher.score("Thunderbird", "thnu").is_some());
+        assert_eq!(TypoMatcher.score("Firefox", "fx"), None);
+        assert_eq!(TypoMatcher.score("Firefox", "chrome"), None);
+        assert_eq!(
+            TypoMatcher.indices("Web Firefox", "firfeox").unwrap().1,
+            [4, 5, 6, 7, 8, 9, 10]
+        );
+    }
+
+    #[test]
+    fn every_word() {
+        let matcher = build_matcher(&[MatcherKind::Skim]);
+        assert!(matcher.score("Visual Studio Code", "code visual").is_some());
+        assert!(matcher.score("Text Editor", "editor  text ").is_some());
+        assert_eq!(matcher.score("Text Editor", "editor xyz"), None);
+        assert_eq!(
+            matcher.score("Firefox Web Browser", "web browser"),
+            SkimMatcher.score("Firefox Web Browser", "web browser")
+        );
+        assert_eq!(
+            matcher.indices("Visual Studio Code", "code vis").unwrap().1,
+            [0, 1, 2, 14, 15, 16, 17]
+        );
+    }
+
+    #[test]
+    fn match_indices() {
+        let indices =
+            |matcher: &dyn Matcher, name, search| matcher.indices(name, search).unwrap().1;
+        assert_eq!(
+            indices(&AcronymMatcher, "Visual Studio Code", "vsc"),
+            [0, 7, 14]
+        );
+        assert_eq!(indices(&SubstringMatcher, "Réseau Fox", "fox"), [7, 8, 9]);
+        assert_eq!(indices(&PrefixMatcher, "Firefox", "fi"), [0, 1]);
+        assert_eq!(
+            indices(&RegexMatcher::default(), "Firefox", "f.x"),
+            [4, 5, 6]
+        );
+        let matcher = build_matcher(&[MatcherKind::Skim, MatcherKind::Acronym]);
+        assert_eq!(
+            indices(&*matcher, "GNU Image Manipulation Program", "gimp"),
+            [0, 4, 10, 23]
+        );
+    }
+}
@@ -0,0 +1,45 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use serde_derive::{Deserialize, Serialize};
+
+/// Ranking settings the user made for a single app.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Overrides {
+    /// Rank the app above every unpinned app that matches the search.
+    pub pinned: bool,
+    /// Factor the app's rank score is multiplied by.
+    pub boost: f64,
+    /// Never show the app in the results.
+    pub hidden: bool,
+}
+
+impl Default for Overrides {
+    fn default() -> Self {
+        Overrides {
+            pinned: false,
+            boost: 1.0,
+            hidden: false,
+        }
+    }
+}
+
+impl Overrides {
+    /// If these are the settings of an app the user never changed.
+    pub fn is_default(&self) -> bool {
+        *self == Overrides::default()
+    }
+}
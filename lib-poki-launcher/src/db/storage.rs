@@ -0,0 +1,114 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::format;
+use log::*;
+use nix::fcntl::{flock, FlockArg};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read as _, Write as _};
+use std::os::unix::io::AsRawFd as _;
+use std::path::{Path, PathBuf};
+
+/// Path of a file next to `path` with `suffix` appended to its name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Path of the rotated backup of the database at `path`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// Move an unreadable database at `path` out of the way.
+///
+/// The file is renamed to `<path>.corrupt-<seconds since the epoch>` so a
+/// fresh database can be written without losing the old one.
+pub fn set_aside(path: &Path, now: u64) -> io::Result<PathBuf> {
+    let aside = with_suffix(path, &format!(".corrupt-{}", now));
+    fs::rename(path, &aside)?;
+    Ok(aside)
+}
+
+/// An exclusive advisory lock on a database file.
+///
+/// Writers hold this while replacing the file so two processes never
+/// interleave their writes. The lock is released when this is dropped.
+pub struct WriteLock {
+    _file: File,
+}
+
+impl WriteLock {
+    /// Block until the lock for the database at `path` is acquired.
+    pub fn acquire(path: &Path) -> io::Result<WriteLock> {
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(with_suffix(path, ".lock"))?;
+        flock(file.as_raw_fd(), FlockArg::LockExclusive).map_err(io::Error::other)?;
+        Ok(WriteLock { _file: file })
+    }
+}
+
+/// Copy the current database to its backup before it's replaced.
+///
+/// A file that doesn't parse is never rotated so a good backup isn't
+/// overwritten by a corrupt one.
+pub fn rotate_backup(path: &Path) {
+    let mut buf = Vec::new();
+    match File::open(path).and_then(|mut file| file.read_to_end(&mut buf)) {
+        Ok(_) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!("Not backing up {}: {}", path.display(), e);
+            return;
+        }
+    }
+    if let Err(e) = format::decode(&buf) {
+        warn!("Not backing up unreadable {}: {}", path.display(), e);
+        return;
+    }
+    let backup = backup_path(path);
+    // A stale backup would make the hard link fail, copying covers the rest.
+    let _ = fs::remove_file(&backup);
+    if let Err(e) = fs::hard_link(path, &backup).or_else(|_| fs::copy(path, &backup).map(|_| ())) {
+        warn!("Failed to back up {}: {}", path.display(), e);
+    }
+}
+
+/// Create the temporary file the new database is written to.
+pub fn create_temp(path: &Path) -> io::Result<(File, PathBuf)> {
+    let temp = with_suffix(path, ".tmp");
+    Ok((File::create(&temp)?, temp))
+}
+
+/// Flush the temporary file to disk and move it over `path`.
+///
+/// The rename is atomic, so readers see either the old or the new file.
+pub fn commit_temp(mut file: File, temp: &Path, path: &Path, buf: &[u8]) -> io::Result<()> {
+    file.write_all(buf)?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(temp, path)?;
+    match path.parent() {
+        // Persist the rename itself.
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all()?,
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use super::{normalize_query, AppsDB, UsageContext};
+use crate::App;
+use serde_derive::{Deserialize, Serialize};
+
+/// The usage of an app before its last launch, to undo the launch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LastLaunch {
+    /// Uuid of the launched app.
+    uuid: String,
+    /// The query the app was launched from, in the form it's learned in.
+    query: String,
+    /// Time of the launch in seconds since the Unix epoch.
+    time: f64,
+    /// Frecency of the app right before the launch.
+    frecency: f64,
+    /// Learned score of the app for `query` right before the launch.
+    learned: f64,
+    /// When the app was used before the launch.
+    context: UsageContext,
+    /// When the app was last launched before this launch.
+    last_used: f64,
+    /// The launch count of the app before the launch.
+    #[serde(default)]
+    launch_count: f64,
+    /// The launch times of the app before the launch.
+    #[serde(default)]
+    launch_times: Vec<f64>,
+}
+
+impl AppsDB {
+    /// Record a launch of `app` after typing `query`.
+    ///
+    /// This is `update` followed by `learn`, keeping what the app's usage
+    /// was before so the launch can be reverted with `undo_launch`.
+    pub fn launch(&mut self, query: &str, app: &App) {
+        self.rebase();
+        let elapsed = self.secs_elapsed();
+        let query = normalize_query(query);
+        let learned = self
+            .learned
+            .get(&query)
+            .and_then(|apps| apps.get(&app.uuid))
+            .map_or(0.0, |score| score / 2.0f64.powf(elapsed / self.half_life));
+        self.last_launch = self
+            .apps
+            .iter()
+            .find(|known| known.uuid == app.uuid)
+            .map(|known| LastLaunch {
+                uuid: known.uuid.clone(),
+                query: query.clone(),
+                time: self.clock.secs(),
+                frecency: known.get_frecency(elapsed, self.half_life),
+                learned,
+                context: known.context.clone(),
+                last_used: known.last_used,
+                launch_count: known.launch_count,
+                launch_times: known.launch_times.clone(),
+            });
+        self.update(app);
+        self.learn(&query, app);
+    }
+
+    /// Revert the last launch recorded with `launch`.
+    ///
+    /// The app's usage is set back to what it was before the launch, decayed
+    /// by the time since, as if the launch never happened. Returns the app,
+    /// or `None` if there's no launch to undo or the app was removed. Only
+    /// the last launch can be undone.
+    pub fn undo_launch(&mut self) -> Option<&App> {
+        let last = self.last_launch.take()?;
+        self.rebase();
+        let elapsed = self.secs_elapsed();
+        let half_life = self.half_life;
+        let decay = 2.0f64.powf(-(self.clock.secs() - last.time) / half_life);
+        if !last.query.is_empty() {
+            if let Some(apps) = self.learned.get_mut(&last.query) {
+                if last.learned > 0.0 {
+                    let growth = 2.0f64.powf(elapsed / half_life);
+                    apps.insert(last.uuid.clone(), last.learned * decay * growth);
+                } else {
+                    apps.remove(&last.uuid);
+                }
+                if apps.is_empty() {
+                    self.learned.remove(&last.query);
+                }
+            }
+        }
+        let app = self.apps.iter_mut().find(|app| app.uuid == last.uuid)?;
+        app.set_frecency(last.frecency * decay, elapsed, half_life);
+        app.context = last.context;
+        app.last_used = last.last_used;
+        app.launch_count = last.launch_count;
+        app.launch_times = last.launch_times;
+        Some(app)
+    }
+}
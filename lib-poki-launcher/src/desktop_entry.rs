@@ -96,7 +96,7 @@ fn strip_entry_args(exec: &str) -> String {
 pub fn parse_desktop_file(path: impl AsRef<Path>) -> Result<Option<App>, Error> {
     let path_str = path.as_ref().to_string_lossy().into_owned();
     // TODO Finish implementation
-    let file = Ini::load_from_file(path).map_err(|e| EntryParseError::InvalidIni {
+    let file = Ini::load_from_file(path.as_ref()).map_err(|e| EntryParseError::InvalidIni {
         file: path_str.clone(),
         err: e.into(),
     })?;
@@ -135,7 +135,13 @@ pub fn parse_desktop_file(path: impl AsRef<Path>) -> Result<Option<App>, Error>
             file: path_str.clone(),
         })?
         .clone();
-    Ok(Some(App::new(name, icon, exec)))
+    let mut app = App::new(name, icon, exec);
+    app.desktop_id = path
+        .as_ref()
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(Some(app))
 }
 
 #[cfg(test)]
@@ -186,6 +192,7 @@ mod test {
             );
             // Note, apps will have different uuids but Eq doesn't consider them
             assert_eq!(app, other_app);
+            assert_eq!(app.desktop_id, "test.desktop");
             remove_file(&path).unwrap();
         }
     }
@@ -0,0 +1,339 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::config::HistoryConfig;
+use crate::db::current_time_secs;
+use crate::App;
+use failure::{Error, Fail};
+use itertools::Itertools as _;
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead as _, BufReader, Write as _};
+use std::path::{Path, PathBuf};
+
+const SECS_PER_DAY: f64 = 60.0 * 60.0 * 24.0;
+/// The log is pruned on a launch once it's larger than this many bytes for
+/// each entry kept, about twice the size of a typical line.
+const PRUNE_BYTES_PER_ENTRY: u64 = 256;
+
+/// A single launch of an app.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Launch {
+    /// Time of the launch in seconds since the Unix epoch.
+    pub timestamp: f64,
+    /// Uuid of the launched app.
+    pub app: String,
+    /// Name of the app when it was launched.
+    pub name: String,
+    /// The search text typed before launching.
+    pub query: String,
+    /// Position of the app in the result list, starting at 0.
+    pub position: usize,
+}
+
+impl Launch {
+    /// Record a launch of `app` happening now.
+    pub fn new(app: &App, query: &str, position: usize) -> Launch {
+        Launch {
+            timestamp: current_time_secs(),
+            app: app.uuid.clone(),
+            name: app.name.clone(),
+            query: query.to_owned(),
+            position,
+        }
+    }
+}
+
+/// An error from reading or writing the history log.
+#[derive(Debug, Fail)]
+pub enum HistoryError {
+    #[fail(display = "Failed to open history file {}: {}", file, err)]
+    FileOpen { file: String, err: Error },
+    #[fail(display = "Failed to write to history file {}: {}", file, err)]
+    FileWrite { file: String, err: Error },
+}
+
+/// The append-only log of launches.
+///
+/// The log is stored as one JSON object per line so a launch can be added
+/// without rewriting the file.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// Open the history log at `path`.
+    ///
+    /// The file is created on the first launch recorded.
+    pub fn new(path: impl AsRef<Path>) -> History {
+        History {
+            path: path.as_ref().to_owned(),
+        }
+    }
+
+    fn path_str(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    /// Append `launch` to the log.
+    ///
+    /// Entries outside of `retention` are only dropped once the file grows
+    /// past a size threshold, so most launches don't read the whole log.
+    pub fn record(&self, launch: &Launch, retention: &HistoryConfig) -> Result<(), Error> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| HistoryError::FileOpen {
+                file: self.path_str(),
+                err: e.into(),
+            })?;
+        writeln!(file, "{}", serde_json::to_string(launch)?).map_err(|e| {
+            HistoryError::FileWrite {
+                file: self.path_str(),
+                err: e.into(),
+            }
+        })?;
+        let len = file
+            .metadata()
+            .map_err(|e| HistoryError::FileOpen {
+                file: self.path_str(),
+                err: e.into(),
+            })?
+            .len();
+        if len > retention.max_entries as u64 * PRUNE_BYTES_PER_ENTRY {
+            self.prune(retention, current_time_secs())?;
+        }
+        Ok(())
+    }
+
+    /// Read every launch in the log, oldest first.
+    ///
+    /// Lines that can't be parsed, such as one cut short by a crash, are skipped.
+    pub fn load(&self) -> Result<Vec<Launch>, Error> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(HistoryError::FileOpen {
+                    file: self.path_str(),
+                    err: e.into(),
+                }
+                .into())
+            }
+        };
+        let mut launches = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| HistoryError::FileOpen {
+                file: self.path_str(),
+                err: e.into(),
+            })?;
+            match serde_json::from_str(&line) {
+                Ok(launch) => launches.push(launch),
+                Err(e) => warn!("Skipping bad line in {}: {}", self.path_str(), e),
+            }
+        }
+        Ok(launches)
+    }
+
+    /// Drop launches older than the retention period and the oldest launches
+    /// past the maximum number of entries.
+    ///
+    /// The file is only rewritten when something was dropped. Returns the
+    /// number of launches dropped.
+    pub fn prune(&self, retention: &HistoryConfig, now: f64) -> Result<usize, Error> {
+        let launches = self.load()?;
+        let kept = retain(&launches, retention, now);
+        let dropped = launches.len() - kept.len();
+        if dropped > 0 {
+            self.rewrite(kept)?;
+        }
+        Ok(dropped)
+    }
+
+    /// Remove the last launch from the log if it's of the app with `uuid`.
+    ///
+    /// Returns whether a launch was removed.
+    pub fn remove_last(&self, uuid: &str) -> Result<bool, Error> {
+        let mut launches = self.load()?;
+        match launches.last() {
+            Some(launch) if launch.app == uuid => {
+                launches.pop();
+                self.rewrite(&launches)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Replace the log with `launches`.
+    fn rewrite<'a>(&self, launches: impl IntoIterator<Item = &'a Launch>) -> Result<(), Error> {
+        let mut temp = self.path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let mut buf = String::new();
+        for launch in launches {
+            buf.push_str(&serde_json::to_string(launch)?);
+            buf.push('\n');
+        }
+        fs::write(&temp, buf)
+            .and_then(|_| fs::rename(&temp, &self.path))
+            .map_err(|e| HistoryError::FileWrite {
+                file: self.path_str(),
+                err: e.into(),
+            })?;
+        Ok(())
+    }
+}
+
+/// The launches to keep under `retention`, oldest first.
+fn retain<'a>(launches: &'a [Launch], retention: &HistoryConfig, now: f64) -> &'a [Launch] {
+    let cutoff = now - retention.max_age_days as f64 * SECS_PER_DAY;
+    let start = launches
+        .iter()
+        .position(|launch| launch.timestamp >= cutoff)
+        .unwrap_or(launches.len())
+        .max(launches.len().saturating_sub(retention.max_entries));
+    &launches[start..]
+}
+
+/// Usage statistics over a period of the launch history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    /// Number of launches in the period.
+    pub launches: usize,
+    /// Mean length of the query typed before a launch, in characters.
+    pub avg_query_len: f64,
+    /// The most launched apps by name with their launch counts, most used first.
+    pub top_apps: Vec<(String, usize)>,
+}
+
+impl Stats {
+    /// Compute statistics of the launches made in the last `days` days.
+    ///
+    /// All launches are included if `days` is `None`.
+    pub fn compute(launches: &[Launch], days: Option<f64>, now: f64, num_top: usize) -> Stats {
+        let cutoff = days.map_or(f64::MIN, |days| now - days * SECS_PER_DAY);
+        let launches: Vec<_> = launches
+            .iter()
+            .filter(|launch| launch.timestamp >= cutoff)
+            .collect();
+        let avg_query_len = if launches.is_empty() {
+            0.0
+        } else {
+            launches
+                .iter()
+                .map(|launch| launch.query.chars().count())
+                .sum::<usize>() as f64
+                / launches.len() as f64
+        };
+        let top_apps = launches
+            .iter()
+            .map(|launch| launch.name.clone())
+            .sorted()
+            .group_by(|name| name.clone())
+            .into_iter()
+            .map(|(name, group)| (name, group.count()))
+            .sorted_by(|left, right| right.1.cmp(&left.1).then(left.0.cmp(&right.0)))
+            .take(num_top)
+            .collect();
+        Stats {
+            launches: launches.len(),
+            avg_query_len,
+            top_apps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn launch(name: &str, query: &str, timestamp: f64) -> Launch {
+        Launch {
+            timestamp,
+            app: name.to_lowercase(),
+            name: name.to_owned(),
+            query: query.to_owned(),
+            position: 0,
+        }
+    }
+
+    #[test]
+    fn retain_by_age_and_count() {
+        let now = 100.0 * SECS_PER_DAY;
+        let launches: Vec<_> = (0..10)
+            .map(|day| launch("App", "a", now - (10 - day) as f64 * SECS_PER_DAY))
+            .collect();
+        let retention = HistoryConfig {
+            max_entries: 100,
+            max_age_days: 5,
+        };
+        assert_eq!(retain(&launches, &retention, now), &launches[5..]);
+        let retention = HistoryConfig {
+            max_entries: 2,
+            max_age_days: 5,
+        };
+        assert_eq!(retain(&launches, &retention, now), &launches[8..]);
+    }
+
+    #[test]
+    fn record_prunes_past_threshold() {
+        let dir = TempDir::new("poki-test").unwrap();
+        let history = History::new(dir.path().join("history.jsonl"));
+        let retention = HistoryConfig {
+            max_entries: 2,
+            max_age_days: 365,
+        };
+        let now = current_time_secs();
+        for _ in 0..3 {
+            history
+                .record(&launch("App", "a", now), &retention)
+                .unwrap();
+        }
+        // Still under the threshold, so nothing is dropped yet.
+        assert_eq!(history.load().unwrap().len(), 3);
+        let long_query = "a".repeat(PRUNE_BYTES_PER_ENTRY as usize * 2);
+        history
+            .record(&launch("App", &long_query, now), &retention)
+            .unwrap();
+        assert_eq!(history.load().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn stats_per_period() {
+        let now = 10.0 * SECS_PER_DAY;
+        let launches = vec![
+            launch("Firefox", "fire", now - 3.0 * SECS_PER_DAY),
+            launch("Terminal", "te", now - 2.0 * SECS_PER_DAY),
+            launch("Terminal", "t", now - 60.0),
+            launch("Firefox", "f", now - 30.0),
+            launch("Terminal", "", now - 10.0),
+        ];
+        let all = Stats::compute(&launches, None, now, 5);
+        assert_eq!(all.launches, 5);
+        assert_eq!(all.avg_query_len, 8.0 / 5.0);
+        assert_eq!(
+            all.top_apps,
+            vec![("Terminal".to_owned(), 3), ("Firefox".to_owned(), 2)]
+        );
+        let day = Stats::compute(&launches, Some(1.0), now, 1);
+        assert_eq!(day.launches, 3);
+        assert_eq!(day.top_apps, vec![("Terminal".to_owned(), 2)]);
+    }
+}
@@ -14,17 +14,24 @@
  * You should have received a copy of the GNU General Public License
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
+/// Seed app usage from other launchers and shells
+pub mod bootstrap;
 /// Application configuration
 pub mod config;
 /// Interact with the app database
 pub mod db;
 /// Parse desktop entries
 pub mod desktop_entry;
+/// Log of app launches
+pub mod history;
+/// Separate sets of app usage
+pub mod profile;
 /// Run an app
 pub mod runner;
 /// Scan for desktop entries
 pub mod scan;
 
+use db::UsageContext;
 use directories::{BaseDirs, ProjectDirs};
 use lazy_static::lazy_static;
 use serde_derive::{Deserialize, Serialize};
@@ -37,6 +44,8 @@ use uuid::prelude::*;
 pub mod prelude {
     pub use crate::config::Config;
     pub use crate::db::AppsDB;
+    pub use crate::history::{History, Launch};
+    pub use crate::profile::Profile;
     pub use crate::scan::*;
     pub use crate::App;
     pub use crate::DIRS;
@@ -56,13 +65,32 @@ pub struct App {
     /// The exec string used to run the app.
     exec: String,
     /// Score of the app of the ranking algo.
-    score: f32,
+    score: f64,
     /// Uuid used to uniquely identify this app.
     /// This is saved to find the app later when the list changes.
     pub uuid: String,
     /// Icon name for this app.
     /// The icon name has to be looked up in the system's icon theme to get a file path.
     pub icon: String,
+    /// The times of day and days of the week this app is launched.
+    #[serde(default)]
+    context: UsageContext,
+    /// File name of the desktop entry this app was read from.
+    ///
+    /// Unlike the uuid this stays the same when the entry changes.
+    #[serde(default)]
+    pub desktop_id: String,
+    /// When the app was last launched in seconds since the Unix epoch,
+    /// 0 if it never was.
+    #[serde(default)]
+    last_used: f64,
+    /// How often the app was launched, counting each launch by its weight.
+    #[serde(default)]
+    launch_count: f64,
+    /// When the app was last launched in seconds since the Unix epoch, at
+    /// most `db::MAX_LAUNCH_TIMES` of them, oldest first.
+    #[serde(default)]
+    launch_times: Vec<f64>,
 }
 
 impl App {
@@ -74,14 +102,31 @@ impl App {
             exec,
             uuid: Uuid::new_v4().to_string(),
             score: 0.0,
+            context: UsageContext::default(),
+            desktop_id: String::new(),
+            last_used: 0.0,
+            launch_count: 0.0,
+            launch_times: Vec::new(),
         }
     }
 
-    /// Set this app's name, icon, and exec to the values of the other app.
+    /// If `id` names this app.
+    ///
+    /// An id is the app's desktop entry file name with or without the
+    /// `.desktop` extension, its uuid, or its name ignoring case.
+    pub fn has_id(&self, id: &str) -> bool {
+        (!self.desktop_id.is_empty()
+            && (self.desktop_id == id || self.desktop_id.trim_end_matches(".desktop") == id))
+            || self.uuid == id
+            || self.name.to_lowercase() == id.to_lowercase()
+    }
+
+    /// Set this app's name, icon, exec and desktop id to the values of the other app.
     pub fn merge(&mut self, other: &App) {
         self.name = other.name.clone();
         self.icon = other.icon.clone();
         self.exec = other.exec.clone();
+        self.desktop_id = other.desktop_id.clone();
     }
 }
 
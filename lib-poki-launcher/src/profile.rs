@@ -0,0 +1,121 @@
+/***
+ * This file is part of Poki Launcher.
+ *
+ * Poki Launcher is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * Poki Launcher is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use crate::DIRS;
+use failure::Fail;
+use std::fmt;
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// The name of the profile used when none is chosen.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// An error from choosing a profile.
+#[derive(Debug, Fail)]
+pub enum ProfileError {
+    #[fail(
+        display = "Invalid profile name {}, use only letters, digits, - and _",
+        name
+    )]
+    InvalidName { name: String },
+}
+
+/// A named set of app usage, kept apart from that of other profiles.
+///
+/// Each profile has its own database and launch history. The default
+/// profile keeps them directly in the data directory, others in a
+/// directory of their own under it. Names are case insensitive, they're
+/// kept in lowercase.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    /// The name of the profile, `None` for the default one.
+    name: Option<String>,
+}
+
+impl FromStr for Profile {
+    type Err = ProfileError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.to_lowercase();
+        if name == DEFAULT_PROFILE {
+            return Ok(Profile::default());
+        }
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(ProfileError::InvalidName { name });
+        }
+        Ok(Profile { name: Some(name) })
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name().unwrap_or(DEFAULT_PROFILE))
+    }
+}
+
+impl Profile {
+    /// The name of the profile, `None` for the default one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The directory the profile's files are kept in, created if missing.
+    pub fn dir(&self) -> PathBuf {
+        let mut dir = DIRS.data_dir().to_path_buf();
+        if let Some(name) = &self.name {
+            dir.push("profiles");
+            dir.push(name);
+        }
+        if !dir.exists() {
+            create_dir_all(&dir)
+                .unwrap_or_else(|_| panic!("Failed to create data dir: {}", dir.to_string_lossy()));
+        }
+        dir
+    }
+
+    /// Location of the profile's apps database.
+    pub fn db_path(&self) -> PathBuf {
+        self.dir().join("apps.db")
+    }
+
+    /// Location of the profile's launch history.
+    pub fn history_path(&self) -> PathBuf {
+        self.dir().join("history.jsonl")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!("default".parse::<Profile>().unwrap(), Profile::default());
+        let work: Profile = "work-2".parse().unwrap();
+        assert_eq!(work.name(), Some("work-2"));
+        assert_eq!(work.to_string(), "work-2");
+        assert_eq!("Work-2".parse::<Profile>().unwrap(), work);
+        assert_eq!("Default".parse::<Profile>().unwrap(), Profile::default());
+        for name in &["", "..", "a/b", "two words"] {
+            assert!(name.parse::<Profile>().is_err(), "{}", name);
+        }
+    }
+}
@@ -15,16 +15,83 @@
  * along with Poki Launcher.  If not, see <https://www.gnu.org/licenses/>.
  */
 use failure::Error;
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
-use std::path::Path;
+use std::os::unix::fs::{MetadataExt as _, OpenOptionsExt as _};
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Receiver};
 use std::thread;
 
 const LOCK_FILE_PATH: &str = "/tmp/poki-launcher.pid";
 
+/// Where what to do before showing is passed to the launcher.
+///
+/// The file has a line for each request, `profile <name>` to switch to a
+/// profile or `incognito` to turn on incognito mode. It's in the user's
+/// runtime directory if there is one, or else the temporary directory with
+/// the user id in the file name.
+fn request_file_path() -> PathBuf {
+    use nix::unistd::getuid;
+
+    let dir = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir);
+    dir.join(format!("poki-launcher-{}.request", getuid()))
+}
+
+/// The message for a line of the request file.
+fn request_msg(line: &str) -> Option<Msg> {
+    let line = line.trim();
+    if line == "incognito" {
+        return Some(Msg::Incognito);
+    }
+    line.strip_prefix("profile ")
+        .map(|name| Msg::Profile(name.trim().to_owned()))
+}
+
+/// Replace the file at `path` with `contents`, readable only by this user.
+///
+/// The file is written next to `path` then renamed over it, so it's never
+/// read half written.
+fn write_private(path: &Path, contents: &str) -> Result<(), Error> {
+    use nix::unistd::getpid;
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", getpid()));
+    let _ = fs::remove_file(&temp);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temp)?;
+    file.write_all(contents.as_bytes())?;
+    drop(file);
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// Read a file written by `write_private`.
+///
+/// Returns `None` if it's missing, or owned by or readable by another user.
+fn read_private(path: &Path) -> Option<String> {
+    use nix::unistd::getuid;
+
+    let mut file = File::open(path).ok()?;
+    let meta = file.metadata().ok()?;
+    if meta.uid() != getuid().as_raw() || meta.mode() & 0o077 != 0 {
+        return None;
+    }
+    let mut buf = String::new();
+    file.read_to_string(&mut buf).ok()?;
+    Some(buf)
+}
+
 pub enum Msg {
     Show,
+    /// Switch to the profile with this name and show.
+    Profile(String),
+    /// Turn on incognito mode and show.
+    Incognito,
     Exit,
 }
 
@@ -43,12 +110,22 @@ impl Notifier {
         let mut file = File::create(&LOCK_FILE_PATH)?;
         write!(file, "{}", getpid())?;
         drop(file);
-        let signals = Signals::new(&[SIGUSR1, SIGINT, SIGTERM, SIGQUIT])?;
+        let signals = Signals::new(&[SIGUSR1, SIGUSR2, SIGINT, SIGTERM, SIGQUIT])?;
         let (tx, rx) = mpsc::channel();
         thread::spawn(move || {
             for signal in signals.forever() {
                 match signal {
                     SIGUSR1 => tx.send(Msg::Show).expect("Failed to send show message"),
+                    SIGUSR2 => {
+                        let request = read_private(&request_file_path()).unwrap_or_default();
+                        let mut msgs: Vec<_> = request.lines().filter_map(request_msg).collect();
+                        if msgs.is_empty() {
+                            msgs.push(Msg::Show);
+                        }
+                        for msg in msgs {
+                            tx.send(msg).expect("Failed to send request message");
+                        }
+                    }
                     SIGINT | SIGTERM | SIGQUIT => {
                         tx.send(Msg::Exit).expect("Failed to send show message");
                         break;
@@ -74,12 +151,34 @@ impl Drop for Notifier {
 }
 
 pub fn notify() -> Result<(), Error> {
-    use nix::sys::signal::{kill, Signal};
+    use nix::sys::signal::Signal;
+
+    signal(Signal::SIGUSR1)
+}
+
+/// Tell the running launcher to show, first switching to the profile named
+/// `profile` if there is one and turning on incognito mode if `incognito`.
+pub fn notify_with(profile: Option<&str>, incognito: bool) -> Result<(), Error> {
+    use nix::sys::signal::Signal;
+
+    let mut request = String::new();
+    if let Some(name) = profile {
+        request.push_str(&format!("profile {}\n", name));
+    }
+    if incognito {
+        request.push_str("incognito\n");
+    }
+    write_private(&request_file_path(), &request)?;
+    signal(Signal::SIGUSR2)
+}
+
+fn signal(signal: nix::sys::signal::Signal) -> Result<(), Error> {
+    use nix::sys::signal::kill;
     use nix::unistd::Pid;
 
     let mut file = File::open(&LOCK_FILE_PATH)?;
     let mut buf = String::new();
     file.read_to_string(&mut buf)?;
-    kill(Pid::from_raw(buf.parse()?), Signal::SIGUSR1)?;
+    kill(Pid::from_raw(buf.parse()?), signal)?;
     Ok(())
 }
@@ -41,11 +41,62 @@ Rectangle {
 		apps_model.hide();
 	}
 
+	Connections {
+		target: window
+		// The list is emptied on launch and the database may have been
+		// reloaded, so list the apps again each time the window is shown.
+		onVisibleChanged: {
+			if (window.visible) {
+				apps_model.search(input.text)
+			}
+		}
+	}
+
 	Shortcut {
 		sequence: "F5"
 		onActivated: scan()
 	}
 
+	Shortcut {
+		sequence: "Ctrl+P"
+		onActivated: apps_model.toggle_pinned()
+	}
+
+	Shortcut {
+		sequence: "Ctrl+Up"
+		onActivated: apps_model.boost_selected(1.5)
+	}
+
+	Shortcut {
+		sequence: "Ctrl+Down"
+		onActivated: apps_model.boost_selected(1 / 1.5)
+	}
+
+	Shortcut {
+		sequence: "Ctrl+H"
+		onActivated: apps_model.hide_selected()
+	}
+
+	Shortcut {
+		sequence: "Ctrl+L"
+		onActivated: apps_model.alias_selected()
+	}
+
+	Shortcut {
+		sequence: "Ctrl+Shift+L"
+		onActivated: apps_model.unalias()
+	}
+
+	Shortcut {
+		sequence: "Ctrl+Z"
+		onActivated: apps_model.undo_launch()
+	}
+
+	Shortcut {
+		sequence: "Ctrl+I"
+		onActivated: apps_model.incognito = !apps_model.incognito
+	}
+
     ColumnLayout {
         anchors.fill: parent
         anchors.margins: 0
@@ -67,12 +118,25 @@ Rectangle {
 				anchors.verticalCenter: input_box.verticalCenter
 				font.pixelSize: window.height * 0.1 * 0.4
 				onTextChanged: apps_model.search(text)
+				// List the most used apps before anything is typed.
+				Component.onCompleted: apps_model.search(text)
 				Keys.onUpPressed: apps_model.up()
 				Keys.onDownPressed: apps_model.down()
 				Keys.onReturnPressed: run()
 				Keys.onEscapePressed: hide()
 			}
 
+			Text {
+				id: incognito_ind
+				visible: apps_model.incognito
+				color: "#bd93f9"
+				text: qsTr("incognito")
+				anchors.right: scan_ind.left
+				anchors.verticalCenter: input_box.verticalCenter
+				anchors.rightMargin: input_box.height * 0.1
+				font.pixelSize: input_box.height * 0.3
+			}
+
 			BusyIndicator {
 				id: scan_ind
 				running: apps_model.is_scanning
@@ -117,9 +181,20 @@ Rectangle {
 						Text {
 							Layout.alignment: Qt.AlignLeft
 							color: "#f8f8f2"
-							text: name
+							textFormat: Text.StyledText
+							text: markup
 							font.pixelSize: item.height * 0.4
 						}
+
+						Text {
+							Layout.fillWidth: true
+							Layout.rightMargin: item.height * 0.2
+							horizontalAlignment: Text.AlignRight
+							visible: pinned
+							color: "#bd93f9"
+							text: "\u2605"
+							font.pixelSize: item.height * 0.3
+						}
 					}
 				}
 
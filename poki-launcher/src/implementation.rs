@@ -18,13 +18,14 @@ use super::interface::*;
 use failure::Error;
 use gtk::{Application, IconLookupFlags, IconTheme, IconThemeExt};
 use lazy_static::lazy_static;
+use lib_poki_launcher::db::{AppDBError, SearchIndex};
 use lib_poki_launcher::prelude::*;
 use log::{error, trace, warn};
 use poki_launcher_notifier::{self as notifier, Notifier};
 use poki_launcher_x11::foreground;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 
 const MAX_APPS_SHOWN: usize = 5;
@@ -36,50 +37,182 @@ fn log_errs(errs: &[Error]) {
 }
 
 lazy_static! {
-    pub static ref DB_PATH: PathBuf = {
-        use std::fs::create_dir;
-        let data_dir = DIRS.data_dir();
-        if !data_dir.exists() {
-            create_dir(&data_dir).unwrap_or_else(|_| {
-                panic!("Failed to create data dir: {}", data_dir.to_string_lossy())
-            });
-        }
-        let mut db_file = data_dir.to_path_buf();
-        db_file.push("apps.db");
-        db_file
-    };
+    /// The profile whose database and settings are used.
+    pub static ref PROFILE: RwLock<Profile> = RwLock::new(Profile::default());
     pub static ref SHOW_ON_START: Arc<AtomicBool> = Arc::new(AtomicBool::new(true));
+    pub static ref INCOGNITO_ON_START: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+}
+
+/// Location of the apps database of the current profile.
+///
+/// The profile only changes while the apps mutex is held, so call this with
+/// it held to get the path of the database in it.
+pub fn db_path() -> PathBuf {
+    PROFILE.read().expect("Profile Lock Poisoned").db_path()
+}
+
+/// Location of the launch history of the current profile.
+pub fn history_path() -> PathBuf {
+    PROFILE
+        .read()
+        .expect("Profile Lock Poisoned")
+        .history_path()
 }
 
 pub struct AppsModel {
     emit: AppsModelEmitter,
     model: AppsModelList,
     list: Vec<App>,
+    /// The names in `list` with the characters matching the query in bold.
+    markup: Vec<String>,
     apps: Arc<Mutex<AppsDB>>,
+    index: SearchIndex,
+    query: String,
     selected_item: String,
     window_visible: Arc<AtomicBool>,
-    config: Config,
+    config: Arc<Mutex<Config>>,
     scanning: Arc<AtomicBool>,
+    /// Don't record launches while set.
+    incognito: Arc<AtomicBool>,
+}
+
+/// Rich text of `name` with the characters at `indices` in bold.
+fn bold_indices(name: &str, indices: &[usize]) -> String {
+    let mut markup = String::new();
+    for (i, c) in name.chars().enumerate() {
+        let bold = indices.contains(&i);
+        if bold {
+            markup.push_str("<b>");
+        }
+        match c {
+            '&' => markup.push_str("&amp;"),
+            '<' => markup.push_str("&lt;"),
+            '>' => markup.push_str("&gt;"),
+            c => markup.push(c),
+        }
+        if bold {
+            markup.push_str("</b>");
+        }
+    }
+    markup
+}
+
+/// Apply the settings from the config file to the database.
+pub fn configure(apps: &mut AppsDB, config: &Config) {
+    apps.set_ranking(config.ranking.clone());
+    apps.set_retention(config.retention.clone());
+    apps.set_config_aliases(config.aliases.clone());
+}
+
+/// Load the database at `db_path`, scanning for apps if there's none yet,
+/// and apply `config` to it.
+///
+/// A database that can't be read is moved aside before a new one is written
+/// in its place. One written by a newer Poki Launcher is an error instead, so
+/// it's never overwritten.
+fn load_apps(db_path: &Path, config: &Config) -> Result<AppsDB, Error> {
+    let loaded = if db_path.exists() {
+        Some(AppsDB::load(db_path))
+    } else {
+        None
+    };
+    let mut apps = match loaded {
+        Some(Ok(apps)) => apps,
+        Some(Err(e)) if AppDBError::is_unsupported_version(&e) => return Err(e),
+        loaded => {
+            if let Some(Err(e)) = loaded {
+                error!("Loading database failed: {}", e);
+                let aside = AppsDB::set_aside(db_path)?;
+                warn!("Moved the unreadable database to {}", aside.display());
+            }
+            let (apps, errors) = AppsDB::from_desktop_entries(&config.app_paths);
+            log_errs(&errors);
+            if let Err(e) = apps.save(db_path) {
+                error!("Saving database failed: {}", e);
+            }
+            apps
+        }
+    };
+    configure(&mut apps, config);
+    Ok(apps)
+}
+
+/// Make `profile` the current profile, loading its settings and database.
+fn switch_profile(profile: Profile, apps: &Mutex<AppsDB>, config: &Mutex<Config>) {
+    let new_config = match Config::load_profile(&profile) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Loading config of profile {} failed: {}", profile, e);
+            return;
+        }
+    };
+    let new_apps = match load_apps(&profile.db_path(), &new_config) {
+        Ok(apps) => apps,
+        Err(e) => {
+            error!("Loading database of profile {} failed: {}", profile, e);
+            return;
+        }
+    };
+    // Swap everything under the apps lock so a save never writes the
+    // database of one profile to the path of the other.
+    let mut apps = apps.lock().expect("Apps Mutex Poisoned");
+    *PROFILE.write().expect("Profile Lock Poisoned") = profile;
+    *apps = new_apps;
+    *config.lock().expect("Config Mutex Poisoned") = new_config;
 }
 
 fn setup_notifier(
     mut emit: AppsModelEmitter,
     window_visible: Arc<AtomicBool>,
+    incognito: Arc<AtomicBool>,
+    apps: Arc<Mutex<AppsDB>>,
+    config: Arc<Mutex<Config>>,
 ) -> Result<(), Error> {
     let rx = Notifier::start()?;
     thread::spawn(move || loop {
         use notifier::Msg;
-        match rx.recv().unwrap() {
-            Msg::Show => {
-                window_visible.store(true, Ordering::Relaxed);
-                emit.visible_changed();
-                foreground("Poki Launcher");
+        let profile = match rx.recv().unwrap() {
+            Msg::Show => None,
+            Msg::Profile(name) => match name.parse::<Profile>() {
+                Ok(profile) => Some(profile),
+                Err(e) => {
+                    error!("{}", e);
+                    None
+                }
+            },
+            Msg::Incognito => {
+                incognito.store(true, Ordering::Relaxed);
+                emit.incognito_changed();
+                None
             }
             Msg::Exit => {
                 drop(rx);
                 std::process::exit(0);
             }
+        };
+        let current = PROFILE.read().expect("Profile Lock Poisoned").clone();
+        match profile {
+            Some(profile) if profile != current => switch_profile(profile, &apps, &config),
+            // Pick up changes made from the command line while hidden.
+            _ => match AppsDB::load(db_path()) {
+                Ok(mut new_apps) => {
+                    configure(
+                        &mut new_apps,
+                        &config.lock().expect("Config Mutex Poisoned"),
+                    );
+                    *apps.lock().expect("Apps Mutex Poisoned") = new_apps;
+                }
+                Err(e) if AppDBError::is_unsupported_version(&e) => {
+                    // Saving over it would lose the newer data.
+                    error!("Reloading database failed: {}, exiting", e);
+                    std::process::exit(1);
+                }
+                Err(e) => error!("Reloading database failed: {}", e),
+            },
         }
+        window_visible.store(true, Ordering::Relaxed);
+        emit.visible_changed();
+        foreground("Poki Launcher");
     });
     Ok(())
 }
@@ -89,28 +222,38 @@ impl AppsModelTrait for AppsModel {
         let _application =
             Application::new(Some("info.bengoldberg.poki_launcher"), Default::default())
                 .expect("failed to initialize GTK application");
-        let config = Config::load().unwrap();
-        let apps = if DB_PATH.exists() {
-            AppsDB::load(&*DB_PATH).unwrap()
-        } else {
-            let (apps, errors) = AppsDB::from_desktop_entries(&config.app_paths);
-            log_errs(&errors);
-            apps.save(&*DB_PATH).unwrap();
-            apps
-        };
+        let profile = PROFILE.read().expect("Profile Lock Poisoned").clone();
+        let config = Config::load_profile(&profile).unwrap();
+        let apps = load_apps(&profile.db_path(), &config).unwrap_or_else(|e| {
+            error!("Loading database failed: {}", e);
+            std::process::exit(1);
+        });
+        let config = Arc::new(Mutex::new(config));
+        let apps = Arc::new(Mutex::new(apps));
 
-        setup_notifier(emit.clone(), SHOW_ON_START.clone()).expect("Failed to setup notifier");
+        setup_notifier(
+            emit.clone(),
+            SHOW_ON_START.clone(),
+            INCOGNITO_ON_START.clone(),
+            apps.clone(),
+            config.clone(),
+        )
+        .expect("Failed to setup notifier");
         let scanning = Arc::new(AtomicBool::new(false));
 
         AppsModel {
             emit,
             model,
             list: Vec::new(),
-            apps: Arc::new(Mutex::new(apps)),
+            markup: Vec::new(),
+            apps,
+            index: SearchIndex::default(),
+            query: String::new(),
             selected_item: String::new(),
             window_visible: SHOW_ON_START.clone(),
             config,
             scanning,
+            incognito: INCOGNITO_ON_START.clone(),
         }
     }
 
@@ -146,6 +289,15 @@ impl AppsModelTrait for AppsModel {
         self.scanning.store(value, Ordering::Relaxed);
     }
 
+    fn incognito(&self) -> bool {
+        self.incognito.load(Ordering::Relaxed)
+    }
+
+    fn set_incognito(&mut self, value: bool) {
+        self.incognito.store(value, Ordering::Relaxed);
+        self.emit.incognito_changed();
+    }
+
     fn name(&self, index: usize) -> &str {
         if index < self.list.len() {
             &self.list[index].name
@@ -154,6 +306,14 @@ impl AppsModelTrait for AppsModel {
         }
     }
 
+    fn markup(&self, index: usize) -> &str {
+        if index < self.markup.len() {
+            &self.markup[index]
+        } else {
+            ""
+        }
+    }
+
     fn uuid(&self, index: usize) -> &str {
         if index < self.list.len() {
             &self.list[index].uuid
@@ -170,6 +330,18 @@ impl AppsModelTrait for AppsModel {
         }
     }
 
+    fn pinned(&self, index: usize) -> bool {
+        if index < self.list.len() {
+            self.apps
+                .lock()
+                .expect("Apps Mutex Poisoned")
+                .overrides(&self.list[index].uuid)
+                .pinned
+        } else {
+            false
+        }
+    }
+
     fn scan(&mut self) {
         trace!("Scanning...");
         self.scanning.store(true, Ordering::Relaxed);
@@ -177,15 +349,20 @@ impl AppsModelTrait for AppsModel {
         let mut emit = self.emit.clone();
         let scanning = self.scanning.clone();
         let apps = self.apps.clone();
-        let config = self.config.clone();
+        let app_paths = self
+            .config
+            .lock()
+            .expect("Config Mutex Poisoned")
+            .app_paths
+            .clone();
         thread::spawn(move || {
-            let (app_list, errors) = scan_desktop_entries(&config.app_paths);
-            let apps = {
+            let (app_list, errors) = scan_desktop_entries(&app_paths);
+            let (apps, db_path) = {
                 let mut apps = apps.lock().expect("Apps Mutex Poisoned");
                 apps.merge_new_entries(app_list);
-                apps.clone()
+                (apps.clone(), db_path())
             };
-            if let Err(e) = apps.save(&*DB_PATH) {
+            if let Err(e) = apps.save(db_path) {
                 error!("Saving database failed: {}", e);
             }
             log_errs(&errors);
@@ -197,11 +374,18 @@ impl AppsModelTrait for AppsModel {
 
     fn search(&mut self, text: String) {
         self.model.begin_reset_model();
-        self.list = self
-            .apps
-            .lock()
-            .expect("Apps Mutex Poisoned")
-            .get_ranked_list(&text, Some(MAX_APPS_SHOWN));
+        let apps = self.apps.lock().expect("Apps Mutex Poisoned");
+        let ranked = self.index.search(&apps, &text, MAX_APPS_SHOWN);
+        self.markup = ranked
+            .iter()
+            .map(|ranked| bold_indices(&ranked.app.name, &ranked.indices))
+            .collect();
+        self.list = ranked
+            .into_iter()
+            .map(|ranked| ranked.app.clone())
+            .collect();
+        drop(apps);
+        self.query = text;
         if !self.list.is_empty() {
             self.selected_item = self.list[0].uuid.clone();
         } else {
@@ -253,17 +437,35 @@ impl AppsModelTrait for AppsModel {
             return;
         }
         self.model.begin_reset_model();
-        let app = self
+        let (position, app) = self
             .list
             .iter()
-            .find(|app| app.uuid == self.selected_item)
+            .enumerate()
+            .find(|(_, app)| app.uuid == self.selected_item)
             .unwrap();
         if let Err(err) = app.run() {
             error!("{}", err);
         }
+        if self.incognito.load(Ordering::Relaxed) {
+            self.list.clear();
+            self.model.end_reset_model();
+            return;
+        }
         let mut apps = self.apps.lock().expect("Apps Mutex Poisoned");
-        apps.update(app);
-        apps.save(&*DB_PATH).unwrap();
+        apps.launch(&self.query, app);
+        if let Err(e) = apps.save(db_path()) {
+            error!("Saving database failed: {}", e);
+        }
+        let launch = Launch::new(app, &self.query, position);
+        let retention = self
+            .config
+            .lock()
+            .expect("Config Mutex Poisoned")
+            .history
+            .clone();
+        if let Err(e) = History::new(history_path()).record(&launch, &retention) {
+            error!("Recording launch failed: {}", e);
+        }
         self.list.clear();
         self.model.end_reset_model();
     }
@@ -272,10 +474,16 @@ impl AppsModelTrait for AppsModel {
         if Path::new(&name).is_absolute() {
             name
         } else {
-            let theme = if self.config.icon_theme.is_some() {
+            let icon_theme = self
+                .config
+                .lock()
+                .expect("Config Mutex Poisoned")
+                .icon_theme
+                .clone();
+            let theme = if icon_theme.is_some() {
                 use std::ops::Deref as _;
                 let theme = IconTheme::new();
-                let name = self.config.icon_theme.as_ref().map(|v| v.deref());
+                let name = icon_theme.as_ref().map(|v| v.deref());
                 theme.set_custom_theme(name);
                 theme
             } else {
@@ -302,6 +510,51 @@ impl AppsModelTrait for AppsModel {
         self.emit.visible_changed();
     }
 
+    fn toggle_pinned(&mut self) {
+        self.edit_selected(|apps, uuid| {
+            let pinned = apps.overrides(uuid).pinned;
+            apps.set_pinned(uuid, !pinned);
+        });
+    }
+
+    fn boost_selected(&mut self, factor: f64) {
+        self.edit_selected(|apps, uuid| {
+            let boost = apps.overrides(uuid).boost * factor;
+            apps.set_boost(uuid, boost);
+        });
+    }
+
+    fn hide_selected(&mut self) {
+        self.edit_selected(|apps, uuid| apps.set_hidden(uuid, true));
+    }
+
+    fn alias_selected(&mut self) {
+        let query = self.query.clone();
+        self.edit_selected(|apps, uuid| apps.set_alias(&query, uuid));
+    }
+
+    fn unalias(&mut self) {
+        let query = self.query.clone();
+        self.edit_selected(|apps, _| apps.remove_alias(&query));
+    }
+
+    fn undo_launch(&mut self) {
+        {
+            let mut apps = self.apps.lock().expect("Apps Mutex Poisoned");
+            let uuid = match apps.undo_launch() {
+                Some(app) => app.uuid.clone(),
+                None => return,
+            };
+            if let Err(e) = apps.save(db_path()) {
+                error!("Saving database failed: {}", e);
+            }
+            if let Err(e) = History::new(history_path()).remove_last(&uuid) {
+                error!("Removing launch from history failed: {}", e);
+            }
+        }
+        self.search(self.query.clone());
+    }
+
     fn exit(&mut self) {
         use nix::sys::signal::{kill, Signal};
         use nix::unistd::Pid;
@@ -311,3 +564,26 @@ impl AppsModelTrait for AppsModel {
         }
     }
 }
+
+impl AppsModel {
+    /// Change the settings of the selected app with `edit` then redo the search.
+    fn edit_selected(&mut self, edit: impl FnOnce(&mut AppsDB, &str)) {
+        if self.selected_item.is_empty() {
+            return;
+        }
+        {
+            let mut apps = self.apps.lock().expect("Apps Mutex Poisoned");
+            edit(&mut apps, &self.selected_item);
+            if let Err(e) = apps.save(db_path()) {
+                error!("Saving database failed: {}", e);
+            }
+        }
+        let selected = self.selected_item.clone();
+        self.search(self.query.clone());
+        if self.list.iter().any(|app| app.uuid == selected) {
+            self.model.begin_reset_model();
+            self.selected_item = selected;
+            self.model.end_reset_model();
+        }
+    }
+}
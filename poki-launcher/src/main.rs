@@ -22,9 +22,19 @@ pub mod interface {
 
 use env_logger::Env;
 use human_panic::setup_panic;
-use implementation::{DB_PATH, SHOW_ON_START};
-use lib_poki_launcher::prelude::AppsDB;
+use implementation::{
+    configure, db_path, history_path, INCOGNITO_ON_START, PROFILE, SHOW_ON_START,
+};
+use lib_poki_launcher::bootstrap::{self, Source};
+use lib_poki_launcher::db::current_time_secs;
+use lib_poki_launcher::db::interchange::{self, Format, Strategy};
+use lib_poki_launcher::db::{Ranked, SearchIndex};
+use lib_poki_launcher::history::Stats;
+use lib_poki_launcher::prelude::{AppsDB, Config, History, Profile};
 use poki_launcher_notifier as notifier;
+use std::fs;
+use std::io::{self, Read as _};
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use structopt::StructOpt;
 
@@ -41,6 +51,99 @@ struct Opt {
     /// Start the daemon without showing the launcher window
     #[structopt(long)]
     no_show: bool,
+    /// Stop recording launches, also in a running launcher. Ctrl+I toggles
+    /// this
+    #[structopt(long)]
+    incognito: bool,
+    /// Use the apps database and settings of this profile, switching the
+    /// running launcher to it
+    #[structopt(long, env = "POKI_PROFILE")]
+    profile: Option<Profile>,
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Print the top apps, launch counts and average query length
+    /// from the launch history
+    Stats {
+        /// Number of top apps to list per period
+        #[structopt(long, default_value = "5")]
+        top: usize,
+    },
+    /// Print the apps the launcher shows for a search
+    Search {
+        /// Number of results to print
+        #[structopt(long, default_value = "5")]
+        limit: usize,
+        /// Print how the rank score of each result is made up
+        #[structopt(long)]
+        explain: bool,
+        /// The search text, the most used apps if empty
+        query: Vec<String>,
+    },
+    /// Rank an app above all unpinned apps
+    Pin {
+        /// Name or uuid of the app
+        app: String,
+    },
+    /// Stop pinning an app
+    Unpin {
+        /// Name or uuid of the app
+        app: String,
+    },
+    /// Multiply the rank score of an app by a factor, 1 resets it
+    Boost {
+        /// Name or uuid of the app
+        app: String,
+        /// Factor to multiply the rank score by
+        factor: f64,
+    },
+    /// Never show an app in the results
+    Hide {
+        /// Name or uuid of the app
+        app: String,
+    },
+    /// Show a hidden app in the results again
+    Unhide {
+        /// Name or uuid of the app
+        app: String,
+    },
+    /// List the apps that are pinned, boosted or hidden
+    Overrides,
+    /// Export how often each app is used, to move it to another machine
+    Export {
+        /// Format to write, json or csv
+        #[structopt(long, default_value = "json")]
+        format: Format,
+        /// File to write to, stdout if not given
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+    },
+    /// Import how often each app is used from an export, another launcher
+    /// or a shell history
+    Import {
+        /// Format to read, json or csv
+        #[structopt(long, default_value = "json")]
+        format: Format,
+        /// Seed usage from rofi, ulauncher, bash, zsh or fish instead of an
+        /// export, reading from where they keep it if no file is given
+        #[structopt(long)]
+        from: Option<Source>,
+        /// How to combine the imported usage with the current one,
+        /// replace, sum or max
+        #[structopt(long, default_value = "max")]
+        strategy: Strategy,
+        /// File to read from, stdin if not given
+        #[structopt(parse(from_os_str))]
+        file: Option<PathBuf>,
+    },
+    /// Undo the last launch, as if the app was never launched
+    Undo,
+    /// Drop usage that no longer counts and old launch history, then
+    /// compact the database file
+    Gc,
 }
 
 fn main() {
@@ -53,19 +156,55 @@ fn main() {
 
     let opt = Opt::from_args();
     SHOW_ON_START.store(!opt.no_show, Ordering::Relaxed);
-    if opt.dump_db {
-        use std::fs::File;
-
-        if DB_PATH.exists() {
-            let mut file = File::open(&*DB_PATH).expect("Failed to open db file");
-            let data: AppsDB = rmp_serde::from_read(&mut file).expect("Failed to parse db");
-            println!("{}", serde_json::to_string_pretty(&data).unwrap());
-        } else {
-            eprintln!("Database file doesn't exit");
-            std::process::exit(1);
+    INCOGNITO_ON_START.store(opt.incognito, Ordering::Relaxed);
+    if let Some(profile) = &opt.profile {
+        *PROFILE.write().unwrap() = profile.clone();
+    }
+    if let Some(cmd) = opt.cmd {
+        match cmd {
+            Command::Stats { top } => print_stats(top),
+            Command::Search {
+                limit,
+                explain,
+                query,
+            } => search(&query.join(" "), limit, explain),
+            Command::Pin { app } => edit_overrides(&app, |apps, uuid| apps.set_pinned(uuid, true)),
+            Command::Unpin { app } => {
+                edit_overrides(&app, |apps, uuid| apps.set_pinned(uuid, false))
+            }
+            Command::Boost { app, factor } => {
+                if !(factor > 0.0 && factor.is_finite()) {
+                    eprintln!("The boost factor must be a positive number");
+                    std::process::exit(1);
+                }
+                edit_overrides(&app, |apps, uuid| apps.set_boost(uuid, factor))
+            }
+            Command::Hide { app } => edit_overrides(&app, |apps, uuid| apps.set_hidden(uuid, true)),
+            Command::Unhide { app } => {
+                edit_overrides(&app, |apps, uuid| apps.set_hidden(uuid, false))
+            }
+            Command::Overrides => print_overrides(),
+            Command::Export { format, file } => export(format, file),
+            Command::Import {
+                format,
+                from,
+                strategy,
+                file,
+            } => import(format, from, strategy, file),
+            Command::Undo => undo(),
+            Command::Gc => gc(),
         }
+    } else if opt.dump_db {
+        let data = load_db();
+        println!("{}", serde_json::to_string_pretty(&data).unwrap());
     } else if notifier::is_running() {
-        if let Err(e) = notifier::notify() {
+        let notified = if opt.profile.is_some() || opt.incognito {
+            let profile = opt.profile.as_ref().map(Profile::to_string);
+            notifier::notify_with(profile.as_deref(), opt.incognito)
+        } else {
+            notifier::notify()
+        };
+        if let Err(e) = notified {
             eprintln!("{}", e);
             start_ui();
         }
@@ -85,3 +224,223 @@ fn start_ui() {
         main_cpp(app_name.as_ptr());
     }
 }
+
+fn print_stats(top: usize) {
+    let launches = match History::new(history_path()).load() {
+        Ok(launches) => launches,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let now = current_time_secs();
+    let periods = [
+        ("Last day", Some(1.0)),
+        ("Last week", Some(7.0)),
+        ("Last month", Some(30.0)),
+        ("All time", None),
+    ];
+    for (i, (title, days)) in periods.iter().enumerate() {
+        let stats = Stats::compute(&launches, *days, now, top);
+        if i > 0 {
+            println!();
+        }
+        println!("{}", title);
+        println!("  Launches: {}", stats.launches);
+        println!("  Average query length: {:.1}", stats.avg_query_len);
+        for (rank, (name, count)) in stats.top_apps.iter().enumerate() {
+            println!("  {}. {} ({})", rank + 1, name, count);
+        }
+    }
+}
+
+fn search(query: &str, limit: usize, explain: bool) {
+    let apps = load_db();
+    let ranked = SearchIndex::new(&apps).search(&apps, query, limit);
+    for (rank, ranked) in ranked.iter().enumerate() {
+        println!("{}. {} ({:.3})", rank + 1, ranked.app.name, ranked.score);
+        if explain {
+            print_breakdown(ranked);
+        }
+    }
+}
+
+/// Print the parts of the rank score of `ranked`.
+fn print_breakdown(ranked: &Ranked) {
+    let breakdown = &ranked.breakdown;
+    let mut flags = Vec::new();
+    if breakdown.pinned {
+        flags.push("pinned");
+    }
+    if breakdown.alias {
+        flags.push("alias");
+    }
+    if breakdown.typo {
+        flags.push("typo");
+    }
+    if breakdown.other_layout {
+        flags.push("other layout");
+    }
+    println!(
+        "     match     {:.3}  (score {})",
+        breakdown.fuzzy_term, breakdown.fuzzy
+    );
+    println!(
+        "     frecency  {:.3}  ({:.3} launches)",
+        breakdown.frecency_term, breakdown.frecency
+    );
+    println!(
+        "     learned   {:.3}  ({:.3} launches)",
+        breakdown.learned_term, breakdown.learned
+    );
+    if breakdown.recency_term > 0.0 {
+        println!(
+            "     recency   {:.3}  ({:.3})",
+            breakdown.recency_term, breakdown.recency
+        );
+    }
+    println!("     bonus     {:.3}", breakdown.bonus);
+    println!("     boost     x{}", breakdown.boost);
+    if !flags.is_empty() {
+        println!("     {}", flags.join(", "));
+    }
+}
+
+/// Load the config of the current profile or exit with an error.
+fn load_config() -> Config {
+    let profile = PROFILE.read().unwrap().clone();
+    Config::load_profile(&profile).unwrap_or_else(|e| exit_with(e))
+}
+
+/// Load the apps database with the profile's config applied, or exit with
+/// an error.
+///
+/// Saving collects garbage with the retention settings of the config, so
+/// this has to be used before any command saves.
+fn load_db() -> AppsDB {
+    let db_path = db_path();
+    if !db_path.exists() {
+        eprintln!("Database file doesn't exit");
+        std::process::exit(1);
+    }
+    let mut apps = match AppsDB::load(&db_path) {
+        Ok(apps) => apps,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    configure(&mut apps, &load_config());
+    apps
+}
+
+/// Change the overrides of the app named `app` with `edit` and save them.
+fn edit_overrides(app: &str, edit: impl FnOnce(&mut AppsDB, &str)) {
+    let mut apps = load_db();
+    let uuid = match apps.find_app(app) {
+        Some(app) => app.uuid.clone(),
+        None => {
+            eprintln!("No app named {}", app);
+            std::process::exit(1);
+        }
+    };
+    edit(&mut apps, &uuid);
+    if let Err(e) = apps.save(db_path()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn print_overrides() {
+    let apps = load_db();
+    for (app, overrides) in apps.overridden_apps() {
+        let mut settings = Vec::new();
+        if overrides.pinned {
+            settings.push("pinned".to_owned());
+        }
+        if overrides.boost != 1.0 {
+            settings.push(format!("boost {}", overrides.boost));
+        }
+        if overrides.hidden {
+            settings.push("hidden".to_owned());
+        }
+        println!("{}: {}", app.name, settings.join(", "));
+    }
+}
+
+/// Print `err` and exit.
+fn exit_with(err: impl std::fmt::Display) -> ! {
+    eprintln!("{}", err);
+    std::process::exit(1);
+}
+
+fn export(format: Format, file: Option<PathBuf>) {
+    let apps = load_db();
+    let text = interchange::write(&apps.export(), format).unwrap_or_else(|e| exit_with(e));
+    match file {
+        Some(file) => fs::write(&file, text).unwrap_or_else(|e| exit_with(e)),
+        None => print!("{}", text),
+    }
+}
+
+fn import(format: Format, from: Option<Source>, strategy: Strategy, file: Option<PathBuf>) {
+    let file = file.or_else(|| from.map(Source::default_path));
+    let data = match file {
+        Some(file) => fs::read(&file),
+        None => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data).map(|_| data)
+        }
+    }
+    .unwrap_or_else(|e| exit_with(e));
+    let mut apps = load_db();
+    let records = match from {
+        Some(source) => bootstrap::records(&apps.apps, source, &data),
+        None => interchange::read(&String::from_utf8_lossy(&data), format),
+    }
+    .unwrap_or_else(|e| exit_with(e));
+    let imported = apps.import(&records, strategy);
+    apps.save(db_path()).unwrap_or_else(|e| exit_with(e));
+    println!(
+        "Imported {} of {} apps, {} aren't installed",
+        imported.matched,
+        records.len(),
+        imported.not_installed
+    );
+    if imported.invalid > 0 {
+        println!("Skipped {} with an invalid frecency", imported.invalid);
+    }
+}
+
+fn undo() {
+    let mut apps = load_db();
+    let (uuid, name) = match apps.undo_launch() {
+        Some(app) => (app.uuid.clone(), app.name.clone()),
+        None => exit_with("No launch to undo"),
+    };
+    apps.save(db_path()).unwrap_or_else(|e| exit_with(e));
+    History::new(history_path())
+        .remove_last(&uuid)
+        .unwrap_or_else(|e| exit_with(e));
+    println!("Undid the launch of {}", name);
+}
+
+fn gc() {
+    let config = load_config();
+    let size = || fs::metadata(db_path()).map(|meta| meta.len()).unwrap_or(0);
+    let before = size();
+    let mut apps = load_db();
+    // `save` collects too, this is only run for the counts. Collecting again
+    // finds nothing so doesn't change what's saved.
+    let collected = apps.gc();
+    apps.save(db_path()).unwrap_or_else(|e| exit_with(e));
+    let launches = History::new(history_path())
+        .prune(&config.history, current_time_secs())
+        .unwrap_or_else(|e| exit_with(e));
+    println!("Dropped {} decayed scores", collected.scores);
+    println!("Forgot the usage of {} unused apps", collected.forgotten);
+    println!("Dropped {} learned queries", collected.learned);
+    println!("Dropped {} aliases of removed apps", collected.aliases);
+    println!("Dropped {} launches from the history", launches);
+    println!("Database file went from {} to {} bytes", before, size());
+}